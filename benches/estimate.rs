@@ -0,0 +1,153 @@
+//! Benchmarks of estimating a generated repository with many commits, authors and files.
+//!
+//! The repository is generated once into Cargo's temporary directory for benchmarks and reused
+//! by later runs. If `git` is installed, a commit-graph is written for it.
+
+use std::{
+    collections::BTreeMap,
+    hint::black_box,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use git_hours::EstimatorConfig;
+use gix::{ObjectId, bstr::BString, date::Time, objs::tree::EntryKind};
+
+const COMMITS: usize = 20_000;
+const AUTHORS: u64 = 50;
+const DIRECTORIES: u64 = 20;
+const FILES: u64 = 10;
+
+/// Open the generated repository, generating it first if needed.
+fn large_repo() -> anyhow::Result<gix::Repository> {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("large-repo-{COMMITS}"));
+    // the marker is written last, so interrupted generations start over
+    let marker = path.join("generated");
+    if !marker.exists() {
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        generate(&path)?;
+        std::fs::write(&marker, "")?;
+    }
+    Ok(gix::open(path)?)
+}
+
+/// Generate a repository with [`COMMITS`] commits on a single branch, made by [`AUTHORS`]
+/// authors in sessions of varying length, each changing a single file.
+fn generate(path: &PathBuf) -> anyhow::Result<()> {
+    let repo = gix::init_bare(path)?;
+    // the ids of the files of every directory, and of the directory trees
+    let mut files: BTreeMap<String, BTreeMap<String, ObjectId>> = BTreeMap::new();
+    let mut directories: BTreeMap<String, ObjectId> = BTreeMap::new();
+
+    // a fixed linear congruential generator keeps the repository the same across runs
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut random = move |bound: u64| {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) % bound
+    };
+
+    let mut parent = None;
+    let mut seconds = 1_600_000_000;
+    for i in 0..COMMITS {
+        // mostly commits within a session, sometimes a break of hours or days
+        seconds += match random(10) {
+            0 => 60 * 60 * (3 + random(48) as i64),
+            _ => 60 * (1 + random(90) as i64),
+        };
+        let author = random(AUTHORS);
+        let signature = gix::actor::Signature {
+            name: format!("Author {author}").into(),
+            email: format!("author{author}@example.com").into(),
+            time: Time::new(seconds, 0),
+        };
+
+        let directory = format!("dir{}", random(DIRECTORIES));
+        let file = format!("file{}.txt", random(FILES));
+        let blob = repo.write_blob(format!("{i}\n").repeat(1 + random(20) as usize))?;
+        let directory_files = files.entry(directory.clone()).or_default();
+        directory_files.insert(file.clone(), blob.detach());
+        let directory_tree = write_tree(&repo, directory_files, EntryKind::Blob)?;
+        directories.insert(directory.clone(), directory_tree);
+        let commit = gix::objs::Commit {
+            tree: write_tree(&repo, &directories, EntryKind::Tree)?,
+            parents: parent.into_iter().collect(),
+            author: signature.clone(),
+            committer: signature,
+            encoding: None,
+            message: BString::from(format!("Change {directory}/{file}\n")),
+            extra_headers: Vec::new(),
+        };
+        parent = Some(repo.write_object(&commit)?.detach());
+    }
+
+    let head = parent.expect("at least one commit");
+    repo.reference(
+        "refs/heads/main",
+        head,
+        gix::refs::transaction::PreviousValue::Any,
+        "generate benchmark repository",
+    )?;
+
+    // the commit-graph is optional, the walk falls back to the object database without it
+    let _ = Command::new("git")
+        .args(["commit-graph", "write", "--reachable"])
+        .current_dir(path)
+        .status();
+    Ok(())
+}
+
+/// Write a tree of `entries` of the same `kind`, which are sorted by name as git requires.
+fn write_tree(
+    repo: &gix::Repository,
+    entries: &BTreeMap<String, ObjectId>,
+    kind: EntryKind,
+) -> anyhow::Result<ObjectId> {
+    let tree = gix::objs::Tree {
+        entries: entries
+            .iter()
+            .map(|(name, id)| gix::objs::tree::Entry {
+                mode: kind.into(),
+                filename: name.as_str().into(),
+                oid: *id,
+            })
+            .collect(),
+    };
+    Ok(repo.write_object(&tree)?.detach())
+}
+
+fn estimate(c: &mut Criterion) {
+    let repo = large_repo().expect("benchmark repository can be generated");
+
+    let mut group = c.benchmark_group("estimate");
+    group.sample_size(10);
+
+    let config = EstimatorConfig::default();
+    group.bench_function("default", |b| {
+        b.iter(|| git_hours::estimate(black_box(&config), &repo).unwrap())
+    });
+
+    for threads in [1, 0] {
+        let config = EstimatorConfig {
+            path_depth: Some(1),
+            threads,
+            ..EstimatorConfig::default()
+        };
+        let name = match threads {
+            0 => "components/all threads".to_string(),
+            threads => format!("components/{threads} thread"),
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| git_hours::estimate(black_box(&config), &repo).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, estimate);
+criterion_main!(benches);
@@ -0,0 +1,394 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use gix::{ObjectId, bstr::BString, date::Time};
+
+use crate::diff::FileChange;
+
+/// Magic bytes at the start of the cache file.
+const MAGIC: &[u8] = b"git-hours cache\0";
+
+/// Version of the cache format. Caches of other versions are discarded.
+const VERSION: u32 = 1;
+
+/// The data of a commit that is independent of the configuration, as stored in the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CachedCommit {
+    pub author_name: BString,
+    pub author_email: BString,
+    pub author_time: Time,
+    pub committer_time: Time,
+    pub num_parents: u32,
+    /// Names and emails from the `Co-authored-by` trailers.
+    pub co_authors: Vec<(BString, BString)>,
+    /// The files changed compared to the first parent, if they were computed. The flag is set if
+    /// lines were counted.
+    pub changes: Option<(bool, Vec<FileChange>)>,
+    /// The patch id, if it was computed.
+    pub patch_id: Option<Option<ObjectId>>,
+}
+
+/// A persistent cache of per-commit data under `<git dir>/git-hours/`, keyed by commit id.
+///
+/// Commits never change, so entries never go stale. The file is append-only: new and updated
+/// entries are appended on [`Self::save`], and later entries replace earlier ones on load.
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    path: Option<PathBuf>,
+    entries: HashMap<ObjectId, CachedCommit>,
+    /// Ids of the entries to append on save.
+    dirty: Vec<ObjectId>,
+    /// Number of entries in the file that were replaced by later ones.
+    superseded: usize,
+}
+
+impl Cache {
+    /// A cache that is neither loaded nor saved, and keeps nothing.
+    pub fn disabled() -> Self {
+        Cache::default()
+    }
+
+    /// Load the cache of `repo`. A missing, corrupt or outdated cache is treated as empty.
+    pub fn load(repo: &gix::Repository) -> Self {
+        Self::load_file(cache_dir(repo).join("commits"))
+    }
+
+    /// Load the cache file at `path`, see [`Self::load`].
+    fn load_file(path: PathBuf) -> Self {
+        let mut cache = Cache {
+            path: Some(path.clone()),
+            ..Cache::default()
+        };
+        let Ok(data) = fs::read(&path) else {
+            return cache;
+        };
+
+        let mut reader = Reader { data: &data };
+        if reader.take(MAGIC.len()) != Some(MAGIC) || reader.u32() != Some(VERSION) {
+            // written by another version, it is replaced on save
+            cache.superseded = usize::MAX;
+            return cache;
+        }
+        while let Some((id, commit)) = reader.entry() {
+            if cache.entries.insert(id, commit).is_some() {
+                cache.superseded += 1;
+            }
+        }
+        if !reader.data.is_empty() {
+            // a truncated last entry, e.g. from an interrupted run, is dropped by a rewrite, as
+            // entries appended after it couldn't be read
+            cache.superseded = usize::MAX;
+        }
+        cache
+    }
+
+    /// The cached data of the commit `id`.
+    pub fn get(&self, id: &ObjectId) -> Option<&CachedCommit> {
+        self.entries.get(id)
+    }
+
+    /// Add or replace the data of the commit `id`.
+    pub fn insert(&mut self, id: ObjectId, commit: CachedCommit) {
+        if self.path.is_none() || self.entries.get(&id) == Some(&commit) {
+            return;
+        }
+        if self.entries.insert(id, commit).is_some() {
+            self.superseded = self.superseded.saturating_add(1);
+        }
+        self.dirty.push(id);
+    }
+
+    /// Write the entries added since loading. The whole file is rewritten if more than half of
+    /// its entries were replaced. Nothing is written while another process holds the lock of the
+    /// cache.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+        let dir = path.parent().expect("cache file is in a directory");
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create cache directory {}", dir.display()))?;
+
+        // runs on the same repository don't write at the same time, the entries of a run that
+        // can't take the lock are written by a later run
+        let Ok(_lock) = gix::lock::Marker::acquire_to_hold_resource(
+            path,
+            gix::lock::acquire::Fail::Immediately,
+            None,
+        ) else {
+            return Ok(());
+        };
+
+        self.dirty.sort_unstable();
+        self.dirty.dedup();
+        let rewrite = !path.exists() || self.superseded > self.entries.len() / 2;
+
+        let write = |path: &PathBuf, ids: &mut dyn Iterator<Item = &ObjectId>, header: bool| {
+            let file = if header {
+                File::create(path)?
+            } else {
+                OpenOptions::new().append(true).open(path)?
+            };
+            let mut out = BufWriter::new(file);
+            if header {
+                out.write_all(MAGIC)?;
+                out.write_all(&VERSION.to_le_bytes())?;
+            }
+            for id in ids {
+                write_entry(&mut out, id, &self.entries[id])?;
+            }
+            out.flush()
+        };
+        if rewrite {
+            // write to a temporary file first, so readers never see a partial cache
+            let tmp = path.with_extension("tmp");
+            write(&tmp, &mut self.entries.keys(), true)?;
+            fs::rename(&tmp, path)?;
+            self.superseded = 0;
+        } else {
+            write(path, &mut self.dirty.iter(), false)?;
+        }
+        self.dirty.clear();
+        Ok(())
+    }
+}
+
+/// The directory of the cache of `repo`, shared by all its worktrees.
+pub(crate) fn cache_dir(repo: &gix::Repository) -> PathBuf {
+    repo.common_dir().join("git-hours")
+}
+
+/// Delete the cache of `repo`, if there is one.
+pub fn clear_cache(repo: &gix::Repository) -> anyhow::Result<()> {
+    let dir = cache_dir(repo);
+    match fs::remove_dir_all(&dir) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to delete cache {}", dir.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_entry(out: &mut impl Write, id: &ObjectId, commit: &CachedCommit) -> std::io::Result<()> {
+    let bytes = |out: &mut dyn Write, bytes: &[u8]| -> std::io::Result<()> {
+        out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        out.write_all(bytes)
+    };
+    let time = |out: &mut dyn Write, time: &Time| -> std::io::Result<()> {
+        out.write_all(&time.seconds.to_le_bytes())?;
+        out.write_all(&time.offset.to_le_bytes())
+    };
+
+    bytes(out, id.as_bytes())?;
+    bytes(out, &commit.author_name)?;
+    bytes(out, &commit.author_email)?;
+    time(out, &commit.author_time)?;
+    time(out, &commit.committer_time)?;
+    out.write_all(&commit.num_parents.to_le_bytes())?;
+    out.write_all(&(commit.co_authors.len() as u32).to_le_bytes())?;
+    for (name, email) in &commit.co_authors {
+        bytes(out, name)?;
+        bytes(out, email)?;
+    }
+    match &commit.changes {
+        None => out.write_all(&[0])?,
+        Some((lines, changes)) => {
+            out.write_all(&[1, u8::from(*lines)])?;
+            out.write_all(&(changes.len() as u32).to_le_bytes())?;
+            for change in changes {
+                bytes(out, &change.path)?;
+                out.write_all(&change.lines.to_le_bytes())?;
+            }
+        }
+    }
+    match &commit.patch_id {
+        None => out.write_all(&[0]),
+        Some(None) => out.write_all(&[1]),
+        Some(Some(id)) => {
+            out.write_all(&[2])?;
+            bytes(out, id.as_bytes())
+        }
+    }
+}
+
+/// Decodes the cache file, returning `None` once the data ends or is malformed.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (taken, rest) = self.data.split_at_checked(len)?;
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn id(&mut self) -> Option<ObjectId> {
+        ObjectId::try_from(self.bytes()?).ok()
+    }
+
+    fn time(&mut self) -> Option<Time> {
+        let seconds = i64::from_le_bytes(self.take(8)?.try_into().ok()?);
+        let offset = i32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        Some(Time::new(seconds, offset))
+    }
+
+    fn entry(&mut self) -> Option<(ObjectId, CachedCommit)> {
+        let id = self.id()?;
+        let author_name = self.bytes()?.into();
+        let author_email = self.bytes()?.into();
+        let author_time = self.time()?;
+        let committer_time = self.time()?;
+        let num_parents = self.u32()?;
+        let co_authors = (0..self.u32()?)
+            .map(|_| Some((self.bytes()?.into(), self.bytes()?.into())))
+            .collect::<Option<_>>()?;
+        let changes = match self.u8()? {
+            0 => None,
+            1 => {
+                let lines = self.u8()? != 0;
+                let changes = (0..self.u32()?)
+                    .map(|_| {
+                        Some(FileChange {
+                            path: self.bytes()?.into(),
+                            lines: self.u32()?,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                Some((lines, changes))
+            }
+            _ => return None,
+        };
+        let patch_id = match self.u8()? {
+            0 => None,
+            1 => Some(None),
+            2 => Some(Some(self.id()?)),
+            _ => return None,
+        };
+        Some((
+            id,
+            CachedCommit {
+                author_name,
+                author_email,
+                author_time,
+                committer_time,
+                num_parents,
+                co_authors,
+                changes,
+                patch_id,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gix::hash::Kind;
+
+    use super::*;
+
+    /// A cache file in a fresh temporary directory named after the test.
+    fn cache_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("git-hours-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("commits")
+    }
+
+    fn id(byte: u8) -> ObjectId {
+        ObjectId::from_bytes_or_panic(&[byte; 20])
+    }
+
+    fn commit(name: &str) -> CachedCommit {
+        CachedCommit {
+            author_name: name.into(),
+            author_email: format!("{name}@example.com").into(),
+            author_time: Time::new(1_700_000_000, 3600),
+            committer_time: Time::new(1_700_000_060, -7200),
+            num_parents: 1,
+            co_authors: vec![("Co".into(), "co@example.com".into())],
+            changes: Some((
+                true,
+                vec![FileChange {
+                    path: "src/lib.rs".into(),
+                    lines: 12,
+                }],
+            )),
+            patch_id: Some(Some(ObjectId::empty_tree(Kind::Sha1))),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = cache_path("round-trip");
+        let mut cache = Cache::load_file(path.clone());
+        cache.insert(id(1), commit("a"));
+        cache.insert(
+            id(2),
+            CachedCommit {
+                changes: None,
+                patch_id: Some(None),
+                ..commit("b")
+            },
+        );
+        cache.save().unwrap();
+        // appended to the existing file
+        let mut cache = Cache::load_file(path.clone());
+        cache.insert(
+            id(3),
+            CachedCommit {
+                co_authors: vec![],
+                patch_id: None,
+                ..commit("c")
+            },
+        );
+        cache.save().unwrap();
+
+        let loaded = Cache::load_file(path.clone());
+        assert_eq!(loaded.entries, cache.entries);
+        assert_eq!(loaded.superseded, 0);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn truncated_file() {
+        let path = cache_path("truncated");
+        let mut cache = Cache::load_file(path.clone());
+        cache.insert(id(1), commit("a"));
+        cache.insert(id(2), commit("b"));
+        cache.save().unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 3]).unwrap();
+
+        let mut cache = Cache::load_file(path.clone());
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.superseded, usize::MAX);
+        // the next save rewrites the whole file
+        cache.insert(id(3), commit("c"));
+        cache.save().unwrap();
+        let loaded = Cache::load_file(path.clone());
+        assert_eq!(loaded.entries, cache.entries);
+        assert_eq!(loaded.superseded, 0);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
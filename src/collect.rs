@@ -0,0 +1,808 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use anyhow::Context;
+use gix::{
+    ObjectId,
+    bstr::{BStr, BString, ByteSlice},
+    revision::plumbing::Spec,
+};
+use regex::Regex;
+
+use crate::{
+    CoAuthors, ComponentSplit, EstimatorConfig, Identity, SessionThreshold,
+    cache::{Cache, CachedCommit},
+    diff::{FileChange, changed_files, patch_id},
+    issue_keys, parallel,
+};
+
+/// The data of a single commit the estimate is based on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitInfo {
+    /// Id of the commit.
+    pub id: ObjectId,
+    /// Time of the commit, as selected by [`EstimatorConfig::timestamp`].
+    pub time: gix::date::Time,
+    /// Whether the author and committer times of the commit are more than
+    /// [`EstimatorConfig::rewrite_threshold`] minutes apart.
+    pub rewritten: bool,
+    /// The share of the commit's time credited to the author. Less than 1 if the time is split
+    /// with co-authors, see [`EstimatorConfig::co_authors`].
+    pub share: f64,
+    /// The components the commit changed, with the number of lines changed in each. Empty
+    /// unless components are configured, see [`EstimatorConfig::has_components`].
+    pub components: Vec<(String, u32)>,
+    /// Id of the changes the commit made, see [`EstimatorConfig::dedupe_patches`]. `None` unless
+    /// patches are deduplicated, and for merge commits and commits without changes.
+    pub patch_id: Option<ObjectId>,
+    /// The keys of the issues the commit references. Empty unless an
+    /// [`issue_pattern`](EstimatorConfig::issue_pattern) is configured.
+    pub issues: Vec<String>,
+}
+
+/// The commits of a single author.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthorCommits {
+    /// The canonical display name of the author, which is the name used in their most recent
+    /// commit.
+    pub name: BString,
+    /// The commits, sorted by time in ascending order.
+    pub commits: Vec<CommitInfo>,
+}
+
+/// Walk all selected revisions of `repo` and collect the commits, grouped
+/// by author as configured by [`EstimatorConfig::identity`]. Identities are mapped through the
+/// repository's mailmap and the configured aliases before grouping, so all times of one person
+/// end up in the same list.
+///
+/// If [paths are filtered](EstimatorConfig::has_path_filter), commits are skipped unless their
+/// changes compared to their first parent touch a selected path.
+///
+/// If [patches are deduplicated](EstimatorConfig::dedupe_patches), only the earliest of an
+/// author's commits with the same changes is kept.
+///
+/// Failures to write the [cache](EstimatorConfig::cache) are ignored.
+///
+/// If the config restricts the estimate to a window, commits that are more than
+/// [`EstimatorConfig::max_commit_diff`] minutes outside of it are skipped, as they cannot be
+/// part of a session that overlaps the window. The remaining commits outside of the window are
+/// kept so that [`split_sessions`](crate::split_sessions) can clip sessions crossing its
+/// boundaries. With [`SessionThreshold::Auto`] or
+/// [`dedupe_patches`](EstimatorConfig::dedupe_patches), all commits are kept, as the threshold
+/// of each author is derived from their whole history, and the earliest copy of a patch may be
+/// far outside of the window.
+pub fn get_commit_times_by_author(
+    config: &EstimatorConfig,
+    repo: &gix::Repository,
+) -> anyhow::Result<HashMap<BString, AuthorCommits>> {
+    collect_commits(config, repo, b"".as_bstr(), &mut Vec::new())
+}
+
+/// Like [`get_commit_times_by_author`], but for a repository nested at `prefix` in another one,
+/// whose changed paths are matched against the configured paths and components as if they were
+/// relative to the outer repository. Problems that don't prevent collecting the commits, like a
+/// failure to write the cache, are added to `warnings`.
+pub(crate) fn collect_commits(
+    config: &EstimatorConfig,
+    repo: &gix::Repository,
+    prefix: &BStr,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<HashMap<BString, AuthorCommits>> {
+    let mut cache = if config.cache {
+        Cache::load(repo)
+    } else {
+        Cache::disabled()
+    };
+
+    let (tips, hidden) = resolve_revisions(config, repo)?;
+    let mut candidates = select_commits(config, repo, &mut cache, tips, hidden)?;
+
+    if config.has_components() || config.has_path_filter() || config.dedupe_patches {
+        let sync_repo = repo.clone().into_sync();
+        let diffs = parallel::map(
+            config.threads(),
+            &candidates,
+            || -> anyhow::Result<_> {
+                let mut repo = sync_repo.to_thread_local();
+                // the tree of a commit is usually the parent tree of the next one diffed
+                repo.object_cache_size_if_unset(16 * 1024 * 1024);
+                let resource_cache = repo.diff_resource_cache_for_tree_diff()?;
+                Ok((repo, resource_cache))
+            },
+            |state, candidate| {
+                let (repo, resource_cache) = state
+                    .as_mut()
+                    .map_err(|err| anyhow::anyhow!("failed to prepare diffing commits: {err}"))?;
+                diff_commit(
+                    config,
+                    repo,
+                    resource_cache,
+                    cache.get(&candidate.id),
+                    candidate,
+                )
+            },
+        );
+
+        let mut pathspecs = pathspec_search(config, repo)?;
+        let mut kept = Vec::with_capacity(candidates.len());
+        for (mut candidate, diff) in candidates.into_iter().zip(diffs) {
+            let Diff { changes, patch_id } = diff?;
+            if let Some(cached) = cache.get(&candidate.id) {
+                let mut cached = cached.clone();
+                cached.changes = changes
+                    .clone()
+                    .map(|changes| (counts_lines(config), changes))
+                    .or(cached.changes);
+                if candidate.num_parents <= 1 && config.dedupe_patches {
+                    cached.patch_id = Some(patch_id);
+                }
+                cache.insert(candidate.id, cached);
+            }
+
+            if let Some(changes) = changes.as_ref().or(candidate.changes.as_ref()) {
+                let (mut touched, mut excluded) = (false, false);
+                for change in changes {
+                    let prefixed;
+                    let path = if prefix.is_empty() {
+                        change.path.as_ref()
+                    } else {
+                        prefixed = join_path(prefix, change.path.as_ref());
+                        prefixed.as_bstr()
+                    };
+                    if config.is_path_excluded(path) {
+                        excluded = true;
+                        continue;
+                    }
+                    if !is_selected(&mut pathspecs, path) {
+                        continue;
+                    }
+                    touched = true;
+
+                    if config.has_components() {
+                        let component = config.component_of(path);
+                        match candidate
+                            .components
+                            .iter_mut()
+                            .find(|(c, _)| *c == component)
+                        {
+                            Some((_, lines)) => *lines += change.lines,
+                            None => candidate.components.push((component, change.lines)),
+                        }
+                    }
+                }
+                // commits without changes, like empty commits, are only dropped if pathspecs
+                // select paths, as with `git log -- <pathspec>`
+                if !touched && (!config.pathspecs.is_empty() || excluded) {
+                    continue;
+                }
+            }
+            candidate.patch_id = patch_id;
+            kept.push(candidate);
+        }
+        candidates = kept;
+    }
+
+    // the cache only saves time, so the estimate goes on without it
+    if let Err(err) = cache.save() {
+        warnings.push(format!("failed to write the cache: {err:#}"));
+    }
+
+    let mut commits_by_author: HashMap<BString, AuthorCommits> = HashMap::new();
+    // the time of the commit each author's display name was taken from
+    let mut name_times = HashMap::new();
+    for candidate in candidates {
+        let time = candidate.time;
+        for (key, name) in candidate.identities {
+            let name_time = name_times.entry(key.clone()).or_insert(time);
+            let entry = commits_by_author.entry(key).or_default();
+            if entry.commits.is_empty() || time >= *name_time {
+                *name_time = time;
+                entry.name = name;
+            }
+            entry.commits.push(CommitInfo {
+                id: candidate.id,
+                time,
+                rewritten: candidate.rewritten,
+                share: candidate.share,
+                patch_id: candidate.patch_id,
+                components: candidate.components.clone(),
+                issues: candidate.issues.clone(),
+            });
+        }
+    }
+
+    for author in commits_by_author.values_mut() {
+        sort_commits(config, author);
+    }
+
+    Ok(commits_by_author)
+}
+
+/// Merge the commits collected from several repositories into a single timeline per author, so
+/// that time spent switching between repositories is only counted once.
+///
+/// Commits that are part of several repositories, e.g. of clones or of a repository that is
+/// also a submodule of another one, are only kept once. The display name of an author is taken
+/// from the repository with their most recent commit. If
+/// [patches are deduplicated](EstimatorConfig::dedupe_patches), copies of a commit in other
+/// repositories are dropped as well.
+pub fn merge_commits_by_author(
+    config: &EstimatorConfig,
+    repositories: Vec<HashMap<BString, AuthorCommits>>,
+) -> HashMap<BString, AuthorCommits> {
+    let mut repositories = repositories.into_iter();
+    let mut merged = repositories.next().unwrap_or_default();
+    for commits_by_author in repositories {
+        for (key, mut author) in commits_by_author {
+            let Some(entry) = merged.get_mut(&key) else {
+                merged.insert(key, author);
+                continue;
+            };
+            if author.commits.last().map(|commit| commit.time)
+                > entry.commits.last().map(|commit| commit.time)
+            {
+                entry.name = std::mem::take(&mut author.name);
+            }
+            // clones of the same repository share commits
+            let mut ids: HashSet<_> = entry.commits.iter().map(|commit| commit.id).collect();
+            author.commits.retain(|commit| ids.insert(commit.id));
+            entry.commits.append(&mut author.commits);
+            sort_commits(config, entry);
+        }
+    }
+    merged
+}
+
+/// The checked out submodules of `repo` and their submodules in turn, with their paths relative
+/// to `repo`. Submodules that aren't checked out are skipped.
+pub(crate) fn submodules(
+    repo: &gix::Repository,
+) -> anyhow::Result<Vec<(BString, gix::Repository)>> {
+    let mut found = Vec::new();
+    for submodule in repo.submodules()?.into_iter().flatten() {
+        let path = submodule.path()?.into_owned();
+        let Some(submodule) = submodule
+            .open()
+            .with_context(|| format!("failed to open submodule {path}"))?
+        else {
+            continue;
+        };
+        let nested = submodules(&submodule)?;
+        found.push((path.clone(), submodule));
+        for (nested_path, nested) in nested {
+            found.push((join_path(path.as_ref(), nested_path.as_ref()), nested));
+        }
+    }
+    Ok(found)
+}
+
+/// Join two repository relative paths.
+fn join_path(parent: &BStr, path: &BStr) -> BString {
+    let mut joined = parent.to_owned();
+    joined.push(b'/');
+    joined.extend_from_slice(path);
+    joined
+}
+
+/// Sort the commits of `author` by time and drop copies of earlier commits if patches are
+/// deduplicated.
+fn sort_commits(config: &EstimatorConfig, author: &mut AuthorCommits) {
+    author.commits.sort_by_key(|commit| commit.time);
+    if config.dedupe_patches {
+        let mut seen = HashSet::new();
+        author
+            .commits
+            .retain(|commit| commit.patch_id.is_none_or(|id| seen.insert(id)));
+    }
+}
+
+/// A commit selected by the walk, with the authors it is credited to.
+struct Candidate {
+    id: ObjectId,
+    num_parents: u32,
+    time: gix::date::Time,
+    rewritten: bool,
+    share: f64,
+    /// The identity key and name of everyone the commit is credited to.
+    identities: Vec<(BString, BString)>,
+    issues: Vec<String>,
+    /// The changed files, if they are cached with the needed details.
+    changes: Option<Vec<FileChange>>,
+    components: Vec<(String, u32)>,
+    patch_id: Option<ObjectId>,
+}
+
+/// Walk the history from `tips`, excluding `hidden` and its ancestry, and select the commits that
+/// pass all filters which don't require diffing.
+///
+/// The walk uses the repository's commit-graph if there is one. Commits are only decoded if they
+/// aren't in the `cache` yet, which they are added to, and their message is only read if it is
+/// needed for filters that aren't cached.
+fn select_commits(
+    config: &EstimatorConfig,
+    repo: &gix::Repository,
+    cache: &mut Cache,
+    tips: Vec<ObjectId>,
+    hidden: Vec<ObjectId>,
+) -> anyhow::Result<Vec<Candidate>> {
+    use gix::objs::FindExt;
+
+    let mailmap = if config.mailmap {
+        repo.open_mailmap()
+    } else {
+        gix::mailmap::Snapshot::default()
+    };
+    let branch_issues = match &config.issue_pattern {
+        Some(pattern) => branch_issues(pattern, repo)?,
+        None => HashMap::new(),
+    };
+    let needs_message = !config.grep.is_empty() || config.issue_pattern.is_some();
+
+    let (earliest, latest) = match config.threshold {
+        SessionThreshold::Fixed if !config.dedupe_patches => {
+            let margin = i64::from(config.max_commit_diff) * 60;
+            (
+                config.since.map(|since| since.as_second() - margin),
+                config.until.map(|until| until.as_second() + margin),
+            )
+        }
+        _ => (None, None),
+    };
+
+    let mut walk = repo
+        .rev_walk(tips)
+        .with_hidden(hidden)
+        .use_commit_graph(true);
+    if config.first_parent {
+        walk = walk.first_parent_only();
+    }
+
+    let mut candidates = Vec::new();
+    let mut buf = Vec::new();
+    for info in walk.all()? {
+        let id = info?.id;
+        let commit = match cache.get(&id) {
+            Some(commit) => Cow::Borrowed(commit),
+            None => {
+                let Some(commit) = decode_commit(repo, id, &mut buf)? else {
+                    continue;
+                };
+                cache.insert(id, commit.clone());
+                Cow::Owned(commit)
+            }
+        };
+        let num_parents = commit.num_parents;
+        if !config.merges.includes(num_parents as usize) {
+            continue;
+        }
+
+        let time = config
+            .timestamp
+            .pick(commit.author_time, commit.committer_time);
+        let rewritten = (commit.author_time.seconds - commit.committer_time.seconds).abs()
+            > i64::from(config.rewrite_threshold) * 60;
+        let in_range = earliest.is_none_or(|earliest| time.seconds >= earliest)
+            && latest.is_none_or(|latest| time.seconds <= latest);
+        if !in_range {
+            continue;
+        }
+        let message = if needs_message {
+            repo.objects.find_commit_iter(&id, &mut buf)?.message()?
+        } else {
+            b"".as_bstr()
+        };
+        if !config.includes_message(message) {
+            continue;
+        }
+
+        let mut people = vec![(commit.author_name.as_ref(), commit.author_email.as_ref())];
+        if config.co_authors != CoAuthors::Ignore {
+            people.extend(
+                (commit.co_authors.iter()).map(|(name, email)| (name.as_ref(), email.as_ref())),
+            );
+        }
+        let mut identities: Vec<(BString, BString, BString)> = Vec::new();
+        for (name, email) in people {
+            let person = mailmap.resolve_cow(gix::actor::SignatureRef {
+                name,
+                email,
+                time: "",
+            });
+            let name = person.name.as_ref();
+            let email = config.resolve_alias(person.email.as_ref());
+            let key = identity_key(config, name, email);
+            if !identities.iter().any(|(k, _, _)| *k == key) {
+                identities.push((key, name.to_owned(), email.to_owned()));
+            }
+        }
+        // everyone shares the commit, even those who aren't counted
+        let share = match config.co_authors {
+            CoAuthors::Split => 1.0 / identities.len() as f64,
+            CoAuthors::CreditAll | CoAuthors::Ignore => 1.0,
+        };
+        identities.retain(|(_, name, email)| config.includes_author(name.as_ref(), email.as_ref()));
+        if identities.is_empty() {
+            continue;
+        }
+
+        let issues = match &config.issue_pattern {
+            Some(pattern) => match issue_keys(pattern, message) {
+                keys if keys.is_empty() => branch_issues.get(&id).cloned().unwrap_or_default(),
+                keys => keys,
+            },
+            None => Vec::new(),
+        };
+
+        candidates.push(Candidate {
+            id,
+            num_parents,
+            time,
+            rewritten,
+            share,
+            identities: identities
+                .into_iter()
+                .map(|(key, name, _)| (key, name))
+                .collect(),
+            issues,
+            changes: commit
+                .changes
+                .as_ref()
+                .filter(|(lines, _)| *lines || !counts_lines(config))
+                .map(|(_, changes)| changes.clone()),
+            components: Vec::new(),
+            patch_id: commit.patch_id.flatten(),
+        });
+    }
+
+    Ok(candidates)
+}
+
+/// Decode the parts of the commit `id` that the cache keeps. Returns `None` for commits with
+/// malformed signatures.
+fn decode_commit(
+    repo: &gix::Repository,
+    id: ObjectId,
+    buf: &mut Vec<u8>,
+) -> anyhow::Result<Option<CachedCommit>> {
+    use gix::objs::{FindExt, commit::ref_iter::Token};
+
+    let mut num_parents = 0;
+    let mut author = None;
+    let mut committer_time = None;
+    let mut message = b"".as_bstr();
+    for token in repo.objects.find_commit_iter(&id, buf)? {
+        match token? {
+            Token::Parent { .. } => num_parents += 1,
+            Token::Author { signature } => author = Some(signature),
+            Token::Committer { signature } => committer_time = Some(signature.time()),
+            Token::Message(text) => message = text,
+            _ => {}
+        }
+    }
+
+    let (Some(author), Some(Ok(committer_time))) = (author, committer_time) else {
+        return Ok(None);
+    };
+    let Ok(author_time) = author.time() else {
+        return Ok(None);
+    };
+    Ok(Some(CachedCommit {
+        author_name: author.name.to_owned(),
+        author_email: author.email.to_owned(),
+        author_time,
+        committer_time,
+        num_parents,
+        co_authors: co_authors(message),
+        changes: None,
+        patch_id: None,
+    }))
+}
+
+/// Whether the lines changed in every file are needed, which requires diffing their contents.
+fn counts_lines(config: &EstimatorConfig) -> bool {
+    config.has_components() && config.component_split == ComponentSplit::Lines
+}
+
+/// The parts of a commit that depend on its changes, as far as they weren't cached.
+struct Diff {
+    /// The changed files with their line counts.
+    changes: Option<Vec<FileChange>>,
+    /// The patch id, if patches are deduplicated.
+    patch_id: Option<ObjectId>,
+}
+
+/// Diff the commit of `candidate` against its first parent, unless the needed results are
+/// `cached` already.
+fn diff_commit(
+    config: &EstimatorConfig,
+    repo: &gix::Repository,
+    resource_cache: &mut gix::diff::blob::Platform,
+    cached: Option<&CachedCommit>,
+    candidate: &Candidate,
+) -> anyhow::Result<Diff> {
+    let needs_changes =
+        (config.has_components() || config.has_path_filter()) && candidate.changes.is_none();
+    let cached_patch_id = cached.and_then(|cached| cached.patch_id);
+    let needs_patch_id =
+        config.dedupe_patches && candidate.num_parents <= 1 && cached_patch_id.is_none();
+    if !needs_changes && !needs_patch_id {
+        return Ok(Diff {
+            changes: None,
+            patch_id: cached_patch_id.flatten(),
+        });
+    }
+
+    let commit = repo.find_commit(candidate.id)?;
+    let changes = if needs_changes {
+        Some(changed_files(
+            &commit,
+            resource_cache,
+            counts_lines(config),
+        )?)
+    } else {
+        None
+    };
+    let patch_id = if needs_patch_id {
+        patch_id(&commit, resource_cache)?
+    } else {
+        cached_patch_id.flatten()
+    };
+    Ok(Diff { changes, patch_id })
+}
+
+/// The key an author is grouped by, as configured by [`EstimatorConfig::identity`].
+fn identity_key(config: &EstimatorConfig, name: &BStr, email: &BStr) -> BString {
+    match config.identity {
+        Identity::Email => email.to_owned(),
+        Identity::Name => name.to_owned(),
+        Identity::NameEmail => {
+            let mut key = name.to_owned();
+            key.extend_from_slice(b" <");
+            key.extend_from_slice(email);
+            key.push(b'>');
+            key
+        }
+    }
+}
+
+/// The names and emails of the co-authors named in the `Co-authored-by: Name <email>` trailers
+/// of a commit `message`, i.e. in its last paragraph. Trailers without an email are skipped.
+fn co_authors(message: &BStr) -> Vec<(BString, BString)> {
+    let lines: Vec<_> = message.lines().collect();
+    // paragraphs are separated by blank lines, which may hold whitespace like the `\r` of CRLF
+    // line endings. The subject is never a trailer, even in a single paragraph message.
+    let last_line = lines.iter().rposition(|line| !line.trim().is_empty());
+    let Some(last_blank) = lines[..last_line.unwrap_or(0)]
+        .iter()
+        .rposition(|line| line.trim().is_empty())
+    else {
+        return Vec::new();
+    };
+    lines[last_blank + 1..]
+        .iter()
+        .filter_map(|line| {
+            let (token, value) = line.split_once_str(":")?;
+            if !token.trim().eq_ignore_ascii_case(b"co-authored-by") {
+                return None;
+            }
+            let (name, email) = value.trim().strip_suffix(b">")?.split_once_str("<")?;
+            Some((name.trim().into(), email.trim().into()))
+        })
+        .collect()
+}
+
+/// Map the commits that are only part of a single branch to the issue keys `pattern` extracts
+/// from the branch's name, e.g. `feature/PROJ-123-login`. Local and remote-tracking branches are
+/// considered, and branches with the same keys count as one.
+fn branch_issues(
+    pattern: &Regex,
+    repo: &gix::Repository,
+) -> anyhow::Result<HashMap<ObjectId, Vec<String>>> {
+    let refs = repo.references()?;
+    let mut branches = Vec::new();
+    for prefix in ["refs/heads/", "refs/remotes/"] {
+        for mut reference in refs.prefixed(prefix)?.filter_map(|r| r.ok()) {
+            // symbolic refs like `origin/HEAD` merely point to other branches
+            if reference.target().try_id().is_none() {
+                continue;
+            }
+            if let Ok(commit) = reference.peel_to_commit() {
+                branches.push((reference.name().shorten().to_owned(), commit.id));
+            }
+        }
+    }
+
+    let branches: Vec<_> = branches
+        .into_iter()
+        .map(|(name, tip)| (issue_keys(pattern, name.as_ref()), tip))
+        .collect();
+    let mut issues = HashMap::new();
+    for (keys, tip) in &branches {
+        if keys.is_empty() {
+            continue;
+        }
+        // copies of the branch, like its remote-tracking branch, don't hide its commits
+        let others = branches
+            .iter()
+            .filter(|(other_keys, _)| other_keys != keys)
+            .map(|(_, id)| *id);
+        for info in repo.rev_walk([*tip]).with_hidden(others).all()? {
+            issues.insert(info?.id, keys.clone());
+        }
+    }
+    Ok(issues)
+}
+
+/// Build the search for [`EstimatorConfig::pathspecs`]. Without pathspecs, it matches everything.
+fn pathspec_search(
+    config: &EstimatorConfig,
+    repo: &gix::Repository,
+) -> anyhow::Result<gix::pathspec::Search> {
+    let patterns = config
+        .pathspecs
+        .iter()
+        .map(|spec| {
+            gix::pathspec::parse(spec.as_bytes(), Default::default())
+                .with_context(|| format!("invalid pathspec `{spec}`"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let root = repo.workdir().unwrap_or(repo.git_dir());
+    Ok(gix::pathspec::Search::from_specs(patterns, None, root)?)
+}
+
+/// Whether the file at the repository relative `path` is selected by the `pathspecs`.
+fn is_selected(pathspecs: &mut gix::pathspec::Search, path: &BStr) -> bool {
+    // attributes aren't supported in pathspecs
+    pathspecs
+        .pattern_matching_relative_path(path, Some(false), &mut |_, _, _, _| false)
+        .is_some_and(|m| !m.is_excluded())
+}
+
+/// Resolve the revisions selected by `config` into the commits to start walking from, and the
+/// commits whose ancestry is excluded from the walk.
+///
+/// If nothing but exclusions is selected, the walk starts from all local branches.
+fn resolve_revisions(
+    config: &EstimatorConfig,
+    repo: &gix::Repository,
+) -> anyhow::Result<(Vec<ObjectId>, Vec<ObjectId>)> {
+    let mut tips = Vec::new();
+    let mut hidden = Vec::new();
+
+    for revision in &config.revisions {
+        let spec = repo
+            .rev_parse(revision.as_str())
+            .with_context(|| format!("invalid revision `{revision}`"))?
+            .detach();
+        match spec {
+            Spec::Include(id) => tips.push(peel_to_commit(repo, id)?),
+            Spec::Exclude(id) => hidden.push(peel_to_commit(repo, id)?),
+            Spec::Range { from, to } => {
+                hidden.push(peel_to_commit(repo, from)?);
+                tips.push(peel_to_commit(repo, to)?);
+            }
+            Spec::Merge { theirs, ours } => {
+                let (theirs, ours) = (peel_to_commit(repo, theirs)?, peel_to_commit(repo, ours)?);
+                tips.extend([theirs, ours]);
+                if let Ok(base) = repo.merge_base(theirs, ours) {
+                    hidden.push(base.detach());
+                }
+            }
+            Spec::IncludeOnlyParents(id) => {
+                let commit = repo.find_commit(peel_to_commit(repo, id)?)?;
+                tips.extend(commit.parent_ids().map(|id| id.detach()));
+            }
+            Spec::ExcludeParents(id) => {
+                let commit = repo.find_commit(peel_to_commit(repo, id)?)?;
+                tips.push(commit.id);
+                hidden.extend(commit.parent_ids().map(|id| id.detach()));
+            }
+        }
+    }
+
+    let mut prefixes = Vec::new();
+    if config.all {
+        prefixes.push("refs/");
+        if let Ok(head) = repo.head_commit() {
+            tips.push(head.id);
+        }
+    } else {
+        if config.remotes {
+            prefixes.push("refs/remotes/");
+        }
+        if config.tags {
+            prefixes.push("refs/tags/");
+        }
+    }
+    if prefixes.is_empty() && tips.is_empty() {
+        prefixes.push("refs/heads/");
+    }
+
+    let refs = repo.references()?;
+    for prefix in prefixes {
+        for mut reference in refs.prefixed(prefix)?.filter_map(|r| r.ok()) {
+            // tags may point to trees or blobs, which are skipped
+            if let Ok(commit) = reference.peel_to_commit() {
+                tips.push(commit.id);
+            }
+        }
+    }
+
+    Ok((tips, hidden))
+}
+
+/// Peel the object `id` points to, e.g. an annotated tag, to a commit.
+fn peel_to_commit(repo: &gix::Repository, id: ObjectId) -> anyhow::Result<ObjectId> {
+    Ok(repo.find_object(id)?.peel_to_commit()?.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn co_authors_of(message: &str) -> Vec<(String, String)> {
+        co_authors(message.as_bytes().as_bstr())
+            .into_iter()
+            .map(|(name, email)| (name.to_string(), email.to_string()))
+            .collect()
+    }
+
+    fn pair(name: &str, email: &str) -> (String, String) {
+        (name.into(), email.into())
+    }
+
+    #[test]
+    fn trailers_in_last_paragraph() {
+        let message = "Add login\n\nBody text.\n\nCo-authored-by: Jane Doe <jane@example.com>\n\
+                       Signed-off-by: John <john@example.com>\n\
+                       Co-authored-by: Max <max@example.com>\n\n";
+        assert_eq!(
+            co_authors_of(message),
+            [
+                pair("Jane Doe", "jane@example.com"),
+                pair("Max", "max@example.com")
+            ]
+        );
+        // only the last paragraph holds trailers
+        let message = "Add login\n\nCo-authored-by: Jane <jane@example.com>\n\nBody text.";
+        assert_eq!(co_authors_of(message), []);
+    }
+
+    #[test]
+    fn single_paragraph() {
+        assert_eq!(co_authors_of("Co-authored-by: Jane <jane@example.com>"), []);
+        assert_eq!(
+            co_authors_of("Add login\nCo-authored-by: Jane <jane@example.com>"),
+            []
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let message = "Add login\r\n\r\nCo-authored-by: Jane <jane@example.com>\r\n";
+        assert_eq!(co_authors_of(message), [pair("Jane", "jane@example.com")]);
+        let message = "Add login\r\n \r\nCo-authored-by: Jane <jane@example.com>";
+        assert_eq!(co_authors_of(message), [pair("Jane", "jane@example.com")]);
+    }
+
+    #[test]
+    fn missing_email() {
+        let message = "Add login\n\nCo-authored-by: Jane\nCo-authored-by: Max <max@example.com>";
+        assert_eq!(co_authors_of(message), [pair("Max", "max@example.com")]);
+    }
+
+    #[test]
+    fn token_case() {
+        let message = "Add login\n\nCo-Authored-By: Jane <jane@example.com>\n\
+                       co-authored-by: Max <max@example.com>";
+        assert_eq!(
+            co_authors_of(message),
+            [
+                pair("Jane", "jane@example.com"),
+                pair("Max", "max@example.com")
+            ]
+        );
+    }
+}
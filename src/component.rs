@@ -0,0 +1,120 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::bail;
+use gix::bstr::{BStr, ByteSlice};
+
+use crate::{CommitInfo, EstimatorConfig, Session};
+
+/// The name of the component that time is attributed to if a session did not change any files.
+pub const NO_COMPONENT: &str = "(none)";
+
+/// The name of the component for files matched by neither a [`Component`] nor
+/// [`EstimatorConfig::path_depth`].
+pub const OTHER_COMPONENT: &str = "(other)";
+
+/// A named part of the repository, like `backend`, given by a glob over repository relative
+/// paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Name of the component.
+    pub name: String,
+    /// Glob matched against repository relative paths, e.g. `backend/**`. `*` does not match
+    /// `/`, while `**` does.
+    pub glob: String,
+}
+
+impl Component {
+    /// Whether `path` belongs to this component.
+    pub fn matches(&self, path: &BStr) -> bool {
+        gix::glob::wildmatch(
+            self.glob.as_bytes().as_bstr(),
+            path,
+            gix::glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+        )
+    }
+}
+
+impl FromStr for Component {
+    type Err = anyhow::Error;
+
+    /// Parse a component in the form `name=glob`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let Some((name, glob)) = s.split_once('=') else {
+            bail!("expected a component in the form `name=glob`");
+        };
+        Ok(Component {
+            name: name.trim().to_string(),
+            glob: glob.trim().to_string(),
+        })
+    }
+}
+
+/// How the time of a session is split across the components its commits changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ComponentSplit {
+    /// Split evenly across all changed components.
+    #[default]
+    Even,
+    /// Split by the number of lines added and removed in each component. Sessions that only
+    /// changed binary files are split evenly.
+    Lines,
+}
+
+named_enum!(ComponentSplit {
+    Even => "even",
+    Lines => "lines",
+});
+
+/// The hours of an author spent on a single component.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentHours {
+    /// Name of the component.
+    pub name: String,
+    /// Hours spent on the component.
+    pub hours: f64,
+}
+
+/// Split the hours of `sessions` across the components their commits changed, as configured
+/// by [`EstimatorConfig::component_split`].
+///
+/// `commits` are the commits the sessions were split from. Only commits inside the configured
+/// window are considered. The [`base_hours`](EstimatorConfig::base_hours) are not attributed to
+/// any component. The result is sorted by component name.
+pub fn split_components(
+    config: &EstimatorConfig,
+    commits: &[CommitInfo],
+    sessions: &[Session],
+) -> Vec<ComponentHours> {
+    let mut hours = BTreeMap::new();
+    for session in sessions {
+        let mut weights = BTreeMap::<&str, f64>::new();
+        for commit in &commits[session.range.clone()] {
+            if !config.contains(commit.time) {
+                continue;
+            }
+            for (component, lines) in &commit.components {
+                *weights.entry(component).or_default() += f64::from(*lines);
+            }
+        }
+
+        let total: f64 = weights.values().sum();
+        let session_hours = session.minutes / 60.0;
+        if weights.is_empty() {
+            *hours.entry(NO_COMPONENT.to_string()).or_default() += session_hours;
+        } else if config.component_split == ComponentSplit::Lines && total > 0.0 {
+            for (component, lines) in weights {
+                *hours.entry(component.to_string()).or_default() += session_hours * lines / total;
+            }
+        } else {
+            let share = session_hours / weights.len() as f64;
+            for component in weights.into_keys() {
+                *hours.entry(component.to_string()).or_default() += share;
+            }
+        }
+    }
+
+    hours
+        .into_iter()
+        .map(|(name, hours)| ComponentHours { name, hours })
+        .collect()
+}
@@ -0,0 +1,440 @@
+//! Defaults for the command line options from configuration files and git config.
+//!
+//! Options are looked up by their long name. The defaults are turned into command line arguments
+//! that are parsed before the actual ones, so they are validated like them and the actual
+//! arguments take precedence.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow, bail};
+use clap::{Arg, ArgAction, Command, builder::BoolishValueParser, error::ErrorKind};
+use gix::bstr::ByteSlice;
+
+/// The sources of defaults and their precedence, shown in the help.
+pub const HELP: &str = "\
+Configuration:
+  Defaults for all options except `--path` and `--scan` are read from, in order of precedence:
+
+  1. the command line
+  2. `.git-hours.toml` in the worktree of the (first) repository
+  3. git config keys like `hours.maxCommitDiff` or `hours.excludeBots`
+  4. `$XDG_CONFIG_HOME/git-hours/config.toml`, by default `~/.config/git-hours/config.toml`
+
+  Options that can be given multiple times, like `--alias`, `--component` or `--author-rate`,
+  collect the values of all sources. Other options take the value of the source with the
+  highest precedence. Flags take an optional value, so a flag set by configuration can be
+  turned off with e.g. `--exclude-bots=false`. In TOML files, options are spelled like on the
+  command line, e.g. `max-commit-diff = 90`, and options taking `key=value` pairs can be given
+  as tables:
+
+      exclude-bots = true
+      rate = 80
+
+      [alias]
+      \"jane@old.example.com\" = \"jane@example.com\"
+
+      [component]
+      backend = [\"backend/**\", \"proto/**\"]
+
+      [author-rate]
+      \"jane@example.com\" = 95";
+
+/// Options that select where configuration is read from, so they can't be configured.
+const UNCONFIGURABLE: &[&str] = &["path", "scan", "help", "version"];
+
+/// The defaults read from all sources, by long option name.
+#[derive(Debug, Default)]
+struct Defaults {
+    options: BTreeMap<String, Setting>,
+}
+
+/// The default of a single option.
+#[derive(Debug, Default)]
+struct Setting {
+    /// Whether the option is a flag, whose value is `true` or `false`.
+    flag: bool,
+    values: Vec<String>,
+}
+
+impl Defaults {
+    /// Set the option `key` to `values` read from `source`. Values of options that can be given
+    /// multiple times are added to the ones read before, others replace them.
+    fn set(
+        &mut self,
+        command: &Command,
+        source: &str,
+        key: &str,
+        values: Vec<String>,
+    ) -> anyhow::Result<()> {
+        let Some(arg) = find_arg(command, key) else {
+            bail!("unknown option `{key}` in {source}");
+        };
+        let long = arg.get_long().expect("configurable options are named");
+        let multiple = matches!(arg.get_action(), ArgAction::Append);
+        if !multiple && values.len() != 1 {
+            bail!("option `{key}` in {source} takes a single value");
+        }
+
+        let flag = !arg.get_action().takes_values();
+        for value in &values {
+            if flag {
+                parse_bool(value).with_context(|| format!("invalid `{key}` in {source}"))?;
+                continue;
+            }
+            let arg = format!("--{long}={value}");
+            let matches = command
+                .clone()
+                .try_get_matches_from([command.get_name(), &arg]);
+            if let Err(err) = matches
+                && matches!(
+                    err.kind(),
+                    ErrorKind::InvalidValue | ErrorKind::ValueValidation
+                )
+            {
+                // the first line holds the message, without the usage
+                let message = err.to_string();
+                let message = message.lines().next().unwrap_or_default();
+                bail!(
+                    "invalid `{key}` in {source}: {}",
+                    message.trim_start_matches("error: ")
+                );
+            }
+        }
+
+        let setting = self.options.entry(long.to_string()).or_default();
+        setting.flag = flag;
+        if !multiple {
+            setting.values.clear();
+        }
+        setting.values.extend(values);
+        Ok(())
+    }
+
+    /// The command line arguments setting the defaults.
+    fn into_args(self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        for (long, setting) in self.options {
+            if setting.flag {
+                let set = setting.values.last().map(|value| parse_bool(value));
+                if let Some(Ok(true)) = set {
+                    args.push(format!("--{long}").into());
+                }
+                continue;
+            }
+            for value in setting.values {
+                args.push(format!("--{long}={value}").into());
+            }
+        }
+        args
+    }
+}
+
+/// Let all flags of `command` take an optional boolean value, like `--exclude-bots=false`, so
+/// flags set by configuration can be turned off on the command line.
+pub fn with_flag_values(command: Command) -> Command {
+    command.mut_args(|arg| {
+        if !matches!(arg.get_action(), ArgAction::SetTrue) {
+            return arg;
+        }
+        arg.action(ArgAction::Set)
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_value("false")
+            .default_missing_value("true")
+            .hide_default_value(true)
+            .value_name("BOOL")
+            .hide_possible_values(true)
+            .value_parser(BoolishValueParser::new())
+    })
+}
+
+/// Read the defaults for the command line options of `command` from all sources, see [`HELP`],
+/// and return them as command line arguments. `repo` is the repository whose `.git-hours.toml`
+/// and git config are read, if any.
+pub fn default_args(
+    command: &Command,
+    repo: Option<&gix::Repository>,
+) -> anyhow::Result<Vec<OsString>> {
+    let mut defaults = Defaults::default();
+
+    if let Some(path) = user_config_path() {
+        read_toml(command, &path, &mut defaults)?;
+    }
+    if let Some(repo) = repo {
+        read_git_config(command, repo, &mut defaults)?;
+        if let Some(workdir) = repo.workdir() {
+            read_toml(command, &workdir.join(".git-hours.toml"), &mut defaults)?;
+        }
+    }
+
+    Ok(defaults.into_args())
+}
+
+/// The path of the configuration file of the user.
+fn user_config_path() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => gix::path::env::home_dir()?.join(".config"),
+    };
+    Some(dir.join("git-hours").join("config.toml"))
+}
+
+/// Read the defaults in the TOML file at `path`, if it exists.
+fn read_toml(command: &Command, path: &Path, defaults: &mut Defaults) -> anyhow::Result<()> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    let table: toml::Table = content
+        .parse()
+        .with_context(|| format!("invalid configuration file {}", path.display()))?;
+
+    let source = path.display().to_string();
+    for (key, value) in table {
+        let mut values = Vec::new();
+        toml_values(&value, None, &mut values)
+            .ok_or_else(|| anyhow!("unsupported value of `{key}` in {source}"))?;
+        defaults.set(command, &source, &key, values)?;
+    }
+    Ok(())
+}
+
+/// Collect the values of the TOML `value` as they are given on the command line. Arrays hold
+/// several values, and tables hold `key=value` pairs. Returns `None` for nested tables.
+fn toml_values(value: &toml::Value, key: Option<&str>, values: &mut Vec<String>) -> Option<()> {
+    let scalar = match value {
+        toml::Value::String(value) => value.clone(),
+        toml::Value::Integer(value) => value.to_string(),
+        toml::Value::Float(value) => value.to_string(),
+        toml::Value::Boolean(value) => value.to_string(),
+        toml::Value::Datetime(value) => value.to_string(),
+        toml::Value::Array(array) => {
+            for value in array {
+                toml_values(value, key, values)?;
+            }
+            return Some(());
+        }
+        toml::Value::Table(table) => {
+            if key.is_some() {
+                return None;
+            }
+            for (key, value) in table {
+                toml_values(value, Some(key), values)?;
+            }
+            return Some(());
+        }
+    };
+    values.push(match key {
+        Some(key) => format!("{key}={scalar}"),
+        None => scalar,
+    });
+    Some(())
+}
+
+/// Read the defaults in the `hours` section of the git config of `repo`, including the user's
+/// and system's git config.
+fn read_git_config(
+    command: &Command,
+    repo: &gix::Repository,
+    defaults: &mut Defaults,
+) -> anyhow::Result<()> {
+    let config = repo.config_snapshot();
+    let Some(sections) = config.plumbing().sections_by_name("hours") else {
+        return Ok(());
+    };
+    for section in sections {
+        if section.header().subsection_name().is_some() {
+            continue;
+        }
+        let mut seen = HashSet::new();
+        for name in section.value_names() {
+            let name = name.to_string();
+            if !seen.insert(name.to_ascii_lowercase()) {
+                continue;
+            }
+            let values: Vec<_> = section
+                .values(&name)
+                .iter()
+                .map(|value| value.to_str_lossy().into_owned())
+                .collect();
+            let source = format!("git config `hours.{name}`");
+            defaults.set(command, &source, &name, values)?;
+        }
+    }
+    Ok(())
+}
+
+/// The configurable option of `command` named `key`, by its long name or the name of its value,
+/// ignoring case, dashes and underscores. E.g. `max-commit-diff`, `maxCommitDiff` and
+/// `max_commit_diff` all name the same option.
+fn find_arg<'a>(command: &'a Command, key: &str) -> Option<&'a Arg> {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect::<String>()
+    };
+    let key = normalize(key);
+    command.get_arguments().find(|arg| {
+        let Some(long) = arg.get_long() else {
+            return false;
+        };
+        !UNCONFIGURABLE.contains(&arg.get_id().as_str())
+            && (normalize(long) == key || normalize(arg.get_id().as_str()) == key)
+    })
+}
+
+fn parse_bool(value: &str) -> anyhow::Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        // git config reads implicit values, like `excludeBots` without `= true`, as empty
+        "" | "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => bail!("expected `true` or `false`, got `{value}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::Args;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn args(defaults: Defaults) -> Vec<String> {
+        (defaults.into_args().into_iter())
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn option_names() {
+        let command = Args::command();
+        let long = |key| find_arg(&command, key).and_then(Arg::get_long);
+        for key in [
+            "max-commit-diff",
+            "maxCommitDiff",
+            "max_commit_diff",
+            "MAXCOMMITDIFF",
+        ] {
+            assert_eq!(long(key), Some("max-commit-diff"));
+        }
+        assert_eq!(long("excludeBots"), Some("exclude-bots"));
+        // by the name of the value
+        assert_eq!(long("author_rates"), Some("author-rate"));
+        assert_eq!(long("unknown"), None);
+        assert_eq!(long("path"), None);
+        assert_eq!(long("scan"), None);
+    }
+
+    #[test]
+    fn precedence() {
+        let command = Args::command();
+        let mut defaults = Defaults::default();
+        // in the order the sources are read, from the lowest precedence
+        defaults
+            .set(&command, "user", "max-commit-diff", strings(&["60"]))
+            .unwrap();
+        defaults
+            .set(&command, "user", "alias", strings(&["a@old=a@new"]))
+            .unwrap();
+        defaults
+            .set(&command, "git", "maxCommitDiff", strings(&["90"]))
+            .unwrap();
+        defaults
+            .set(
+                &command,
+                "git",
+                "alias",
+                strings(&["b@old=b@new", "c@old=c@new"]),
+            )
+            .unwrap();
+        assert_eq!(
+            args(defaults),
+            [
+                "--alias=a@old=a@new",
+                "--alias=b@old=b@new",
+                "--alias=c@old=c@new",
+                "--max-commit-diff=90",
+            ]
+        );
+    }
+
+    #[test]
+    fn flags() {
+        let command = Args::command();
+        let flag = |values: &[&[&str]]| {
+            let mut defaults = Defaults::default();
+            for value in values {
+                defaults
+                    .set(&command, "test", "exclude-bots", strings(value))
+                    .unwrap();
+            }
+            args(defaults)
+        };
+        assert_eq!(flag(&[&["true"]]), ["--exclude-bots"]);
+        // `excludeBots` without a value in git config
+        assert_eq!(flag(&[&[""]]), ["--exclude-bots"]);
+        assert_eq!(flag(&[&["yes"], &["off"]]), Vec::<String>::new());
+        assert_eq!(flag(&[&["false"], &["1"]]), ["--exclude-bots"]);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let command = Args::command();
+        let mut defaults = Defaults::default();
+        let mut set = |key, values| defaults.set(&command, "test", key, strings(values));
+        assert!(set("max-commit-diff", &["soon"]).is_err());
+        assert!(set("max-commit-diff", &["60", "90"]).is_err());
+        assert!(set("exclude-bots", &["maybe"]).is_err());
+        assert!(set("threshold", &["sometimes"]).is_err());
+        assert!(set("no-such-option", &["1"]).is_err());
+        assert!(set("threshold", &["auto"]).is_ok());
+    }
+
+    #[test]
+    fn toml_tables() {
+        let table: toml::Table = r#"
+            exclude-bots = true
+            rate = 80.5
+
+            [component]
+            backend = ["backend/**", "proto/**"]
+            web = "web/**"
+        "#
+        .parse()
+        .unwrap();
+        let values = |key| {
+            let mut values = Vec::new();
+            toml_values(&table[key], None, &mut values).map(|()| values)
+        };
+        assert_eq!(values("exclude-bots"), Some(strings(&["true"])));
+        assert_eq!(values("rate"), Some(strings(&["80.5"])));
+        assert_eq!(
+            values("component"),
+            Some(strings(&[
+                "backend=backend/**",
+                "backend=proto/**",
+                "web=web/**"
+            ]))
+        );
+
+        let nested: toml::Table = "[component.backend]\nglob = \"backend/**\""
+            .parse()
+            .unwrap();
+        assert_eq!(
+            toml_values(&nested["component"], None, &mut Vec::new()),
+            None
+        );
+    }
+}
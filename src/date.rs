@@ -0,0 +1,167 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, bail};
+use jiff::{
+    Span, Timestamp, Zoned, civil,
+    tz::{Offset, TimeZone},
+};
+
+/// The time zone calendar based computations like day boundaries are evaluated in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ReferenceTimeZone {
+    /// The offset each commit was recorded with, i.e. the local time of its author.
+    #[default]
+    Commit,
+    /// A fixed time zone for all commits.
+    Zone(TimeZone),
+}
+
+impl ReferenceTimeZone {
+    /// The time zone to evaluate `time` in.
+    pub fn for_time(&self, time: gix::date::Time) -> TimeZone {
+        match self {
+            ReferenceTimeZone::Commit => {
+                TimeZone::fixed(Offset::from_seconds(time.offset).unwrap_or(Offset::UTC))
+            }
+            ReferenceTimeZone::Zone(time_zone) => time_zone.clone(),
+        }
+    }
+
+    /// Convert `time` to a zoned datetime in the time zone it is evaluated in.
+    pub fn to_zoned(&self, time: gix::date::Time) -> anyhow::Result<Zoned> {
+        Ok(Timestamp::from_second(time.seconds)?.to_zoned(self.for_time(time)))
+    }
+}
+
+impl fmt::Display for ReferenceTimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceTimeZone::Commit => f.write_str("commit"),
+            ReferenceTimeZone::Zone(time_zone) => {
+                f.write_str(time_zone.iana_name().unwrap_or("UTC"))
+            }
+        }
+    }
+}
+
+impl FromStr for ReferenceTimeZone {
+    type Err = anyhow::Error;
+
+    /// Parse `commit`, `utc` or an IANA time zone name like `Europe/Berlin`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "commit" => ReferenceTimeZone::Commit,
+            "utc" | "UTC" => ReferenceTimeZone::Zone(TimeZone::UTC),
+            name => ReferenceTimeZone::Zone(
+                TimeZone::get(name).with_context(|| format!("unknown time zone `{name}`"))?,
+            ),
+        })
+    }
+}
+
+/// Parse a date given on the command line into a timestamp.
+///
+/// The following forms are accepted, in this order:
+/// - `now`, `today`, `yesterday`
+/// - `last <weekday>`, e.g. `last monday`: the start of the most recent such day before today
+/// - RFC 3339 timestamps, e.g. `2024-03-01T12:00:00+01:00`
+/// - civil dates and datetimes, e.g. `2024-03-01` or `2024-03-01 12:00`, interpreted in the time
+///   zone of `now`
+/// - relative spans, e.g. `2 weeks ago` or `3 days`. Like git, spans always go into the past,
+///   so `ago` is optional.
+///
+/// Dates without a time of day refer to the start of that day.
+pub fn parse_date(input: &str, now: &Zoned) -> anyhow::Result<Timestamp> {
+    let input = input.trim();
+    let lower = input.to_ascii_lowercase();
+
+    match lower.as_str() {
+        "now" => return Ok(now.timestamp()),
+        "today" => return Ok(now.start_of_day()?.timestamp()),
+        "yesterday" => return Ok(now.yesterday()?.start_of_day()?.timestamp()),
+        _ => {}
+    }
+
+    if let Some(weekday) = lower.strip_prefix("last ") {
+        let weekday = parse_weekday(weekday.trim())
+            .with_context(|| format!("unknown weekday in date `{input}`"))?;
+        return Ok(now.nth_weekday(-1, weekday)?.start_of_day()?.timestamp());
+    }
+
+    if let Ok(timestamp) = input.parse::<Timestamp>() {
+        return Ok(timestamp);
+    }
+    if let Ok(datetime) = input.parse::<civil::DateTime>() {
+        return Ok(datetime.to_zoned(now.time_zone().clone())?.timestamp());
+    }
+    if let Ok(date) = input.parse::<civil::Date>() {
+        return Ok(date.to_zoned(now.time_zone().clone())?.timestamp());
+    }
+    if let Ok(span) = input.parse::<Span>() {
+        return Ok(now.checked_sub(span.abs())?.timestamp());
+    }
+
+    bail!("could not parse date `{input}`")
+}
+
+fn parse_weekday(name: &str) -> Option<civil::Weekday> {
+    use civil::Weekday::*;
+
+    let weekday = match name {
+        "monday" | "mon" => Monday,
+        "tuesday" | "tue" => Tuesday,
+        "wednesday" | "wed" => Wednesday,
+        "thursday" | "thu" => Thursday,
+        "friday" | "fri" => Friday,
+        "saturday" | "sat" => Saturday,
+        "sunday" | "sun" => Sunday,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> String {
+        // a wednesday
+        let now: Zoned = "2024-03-06T15:30:00+01:00[Europe/Berlin]".parse().unwrap();
+        parse_date(input, &now).unwrap().to_string()
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_eq!(parse("now"), "2024-03-06T14:30:00Z");
+        assert_eq!(parse("today"), "2024-03-05T23:00:00Z");
+        assert_eq!(parse("yesterday"), "2024-03-04T23:00:00Z");
+        assert_eq!(parse("2 weeks ago"), "2024-02-21T14:30:00Z");
+        assert_eq!(parse("3 days"), "2024-03-03T14:30:00Z");
+    }
+
+    #[test]
+    fn last_weekday() {
+        assert_eq!(parse("last monday"), "2024-03-03T23:00:00Z");
+        assert_eq!(parse("Last Mon"), "2024-03-03T23:00:00Z");
+        // today doesn't count
+        assert_eq!(parse("last wednesday"), "2024-02-27T23:00:00Z");
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(parse("2024-03-01T12:00:00+01:00"), "2024-03-01T11:00:00Z");
+        assert_eq!(parse("2024-03-01T12:00:00Z"), "2024-03-01T12:00:00Z");
+        assert_eq!(parse("2024-03-01 12:00"), "2024-03-01T11:00:00Z");
+        assert_eq!(parse("2024-03-01"), "2024-02-29T23:00:00Z");
+        // summer time
+        assert_eq!(parse("2024-07-01"), "2024-06-30T22:00:00Z");
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let now = Zoned::now();
+        assert!(parse_date("last week day", &now).is_err());
+        assert!(parse_date("tomorrow", &now).is_err());
+        assert!(parse_date("2024-13-01", &now).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use gix::{
+    ObjectId,
+    bstr::{BStr, BString},
+    diff::blob::platform::prepare_diff::Operation,
+    object::{blob::diff::lines, tree::diff::Action},
+};
+
+/// A file changed by a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Repository relative path of the file.
+    pub path: BString,
+    /// Number of lines added and removed. Always 0 if lines aren't counted, or for binary files.
+    pub lines: u32,
+}
+
+/// Compute the files `commit` changed compared to its first parent, or to the empty tree if it
+/// is a root commit.
+///
+/// Lines are only counted if `count_lines` is set, as that requires diffing the contents of
+/// every changed file. Rename tracking is disabled, so renames show up as deletion and addition.
+pub(crate) fn changed_files(
+    commit: &gix::Commit<'_>,
+    resource_cache: &mut gix::diff::blob::Platform,
+    count_lines: bool,
+) -> anyhow::Result<Vec<FileChange>> {
+    let (parent_tree, tree) = trees(commit)?;
+
+    let mut changes = Vec::new();
+    parent_tree
+        .changes()?
+        .options(|options| {
+            options.track_path().track_rewrites(None);
+        })
+        .for_each_to_obtain_tree(&tree, |change| {
+            if change.entry_mode().is_tree() {
+                return Ok::<_, std::convert::Infallible>(Action::Continue);
+            }
+
+            let lines = if count_lines {
+                let counts = change
+                    .diff(resource_cache)
+                    .ok()
+                    .and_then(|mut platform| platform.line_counts().ok())
+                    .flatten();
+                resource_cache.clear_resource_cache_keep_allocation();
+                counts.map_or(0, |counts| counts.insertions + counts.removals)
+            } else {
+                0
+            };
+
+            changes.push(FileChange {
+                path: change.location().to_owned(),
+                lines,
+            });
+            Ok(Action::Continue)
+        })?;
+
+    Ok(changes)
+}
+
+/// Compute a patch id of the changes `commit` made compared to its first parent, similar to
+/// `git patch-id --stable`. Commits applying the same changes, like cherry-picks and rebased
+/// copies, share the patch id, as it ignores line numbers and whitespace.
+///
+/// Returns `None` if the commit changed nothing.
+pub(crate) fn patch_id(
+    commit: &gix::Commit<'_>,
+    resource_cache: &mut gix::diff::blob::Platform,
+) -> anyhow::Result<Option<ObjectId>> {
+    let (parent_tree, tree) = trees(commit)?;
+
+    let mut hasher = gix::hash::hasher(commit.id.kind());
+    let mut empty = true;
+    parent_tree
+        .changes()?
+        .options(|options| {
+            options.track_path().track_rewrites(None);
+        })
+        .for_each_to_obtain_tree(&tree, |change| {
+            if change.entry_mode().is_tree() {
+                return Ok::<_, std::convert::Infallible>(Action::Continue);
+            }
+            empty = false;
+            hasher.update(change.location());
+            hasher.update(b"\0");
+
+            let mut hunk = |prefix: &[u8], lines: &[&BStr]| {
+                for line in lines {
+                    hasher.update(prefix);
+                    hasher.update(
+                        &line
+                            .iter()
+                            .copied()
+                            .filter(|b| !b.is_ascii_whitespace())
+                            .collect::<Vec<_>>(),
+                    );
+                }
+            };
+            let text = change.diff(resource_cache).ok().and_then(|mut platform| {
+                let outcome = platform
+                    .lines(|change| {
+                        match change {
+                            lines::Change::Addition { lines } => hunk(b"+", lines),
+                            lines::Change::Deletion { lines } => hunk(b"-", lines),
+                            lines::Change::Modification {
+                                lines_before,
+                                lines_after,
+                            } => {
+                                hunk(b"-", lines_before);
+                                hunk(b"+", lines_after);
+                            }
+                        }
+                        Ok::<_, std::convert::Infallible>(())
+                    })
+                    .ok()?;
+                (!matches!(outcome.operation, Operation::SourceOrDestinationIsBinary)).then_some(())
+            });
+            resource_cache.clear_resource_cache_keep_allocation();
+            // binary files are identified by their contents
+            if text.is_none() {
+                hasher.update(change.id().as_bytes());
+            }
+            Ok(Action::Continue)
+        })?;
+
+    Ok((!empty).then(|| hasher.try_finalize()).transpose()?)
+}
+
+/// The trees of the first parent of `commit`, or the empty tree for root commits, and of
+/// `commit` itself.
+fn trees<'repo>(
+    commit: &gix::Commit<'repo>,
+) -> anyhow::Result<(gix::Tree<'repo>, gix::Tree<'repo>)> {
+    let parent_tree = match commit.parent_ids().next() {
+        Some(parent) => parent.object()?.into_commit().tree()?,
+        None => commit.repo.empty_tree(),
+    };
+    Ok((parent_tree, commit.tree()?))
+}
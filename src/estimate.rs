@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use crate::{CommitInfo, EstimatorConfig};
+
+/// A coding session: a run of commits of one author where each commit follows the previous one
+/// within the session threshold, see [`EstimatorConfig::threshold`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    /// Time of the first commit of the session.
+    pub start: gix::date::Time,
+    /// Time of the last commit of the session.
+    pub end: gix::date::Time,
+    /// Number of commits of the session inside the configured window.
+    pub commits: usize,
+    /// Minutes credited for the session, including the
+    /// [`first_commit_add`](EstimatorConfig::first_commit_add) bonus. The time credited for each
+    /// commit is scaled by its [`share`](CommitInfo::share).
+    pub minutes: f64,
+    /// Indices of the session's commits in the commits it was split from, including commits
+    /// outside the configured window.
+    pub range: Range<usize>,
+}
+
+/// Split the sorted `commits` of a single author into coding sessions, where subsequent commits
+/// of a session are less than `threshold` minutes apart.
+///
+/// Sessions crossing the boundaries of the configured window are clipped as described in the
+/// [crate documentation](crate), and sessions entirely outside of it are omitted.
+pub fn split_sessions(
+    config: &EstimatorConfig,
+    threshold: u32,
+    commits: &[CommitInfo],
+) -> Vec<Session> {
+    let max_commit_diff = i64::from(threshold) * 60;
+
+    let mut sessions: Vec<Session> = Vec::new();
+    for (i, commit) in commits.iter().enumerate() {
+        let time = commit.time;
+        let previous = i.checked_sub(1).map(|i| commits[i].time);
+        match previous.filter(|previous| time.seconds - previous.seconds < max_commit_diff) {
+            Some(previous) => {
+                let session = sessions
+                    .last_mut()
+                    .expect("previous commit started a session");
+                session.end = time;
+                session.range.end = i + 1;
+                session.minutes += commit.share
+                    * config.clipped_seconds(previous.seconds, time.seconds) as f64
+                    / 60.0;
+            }
+            None => sessions.push(Session {
+                start: time,
+                end: time,
+                commits: 0,
+                minutes: if config.contains(time) {
+                    commit.share * config.first_commit_add as f64
+                } else {
+                    0.0
+                },
+                range: i..i + 1,
+            }),
+        }
+
+        if config.contains(time) {
+            sessions
+                .last_mut()
+                .expect("commit is part of a session")
+                .commits += 1;
+        }
+    }
+
+    sessions.retain(|session| session.commits > 0 || session.minutes > 0.0);
+    sessions
+}
+
+/// Estimate the hours spent from the `sessions` of a single author, including the
+/// [`base_hours`](EstimatorConfig::base_hours).
+pub fn estimate_hours(config: &EstimatorConfig, sessions: &[Session]) -> f64 {
+    config.base_hours + sessions.iter().map(|session| session.minutes).sum::<f64>() / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use gix::{ObjectId, date::Time, hash::Kind};
+
+    use super::*;
+
+    const MINUTE: i64 = 60;
+
+    fn commit(minutes: i64, share: f64) -> CommitInfo {
+        CommitInfo {
+            id: ObjectId::null(Kind::Sha1),
+            time: Time::new(1_700_000_000 + minutes * MINUTE, 0),
+            rewritten: false,
+            share,
+            components: vec![],
+            patch_id: None,
+            issues: vec![],
+        }
+    }
+
+    fn config() -> EstimatorConfig {
+        EstimatorConfig {
+            first_commit_add: 30,
+            ..EstimatorConfig::default()
+        }
+    }
+
+    #[test]
+    fn single_commit() {
+        let sessions = split_sessions(&config(), 120, &[commit(0, 1.0)]);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].commits, 1);
+        assert_eq!(sessions[0].minutes, 30.0);
+        assert_eq!(estimate_hours(&config(), &sessions), 0.5);
+    }
+
+    #[test]
+    fn sessions_split_by_threshold() {
+        let commits = [
+            commit(0, 1.0),
+            commit(60, 1.0),
+            commit(90, 1.0),
+            // more than two hours later
+            commit(300, 1.0),
+            commit(310, 1.0),
+        ];
+        let sessions = split_sessions(&config(), 120, &commits);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!((sessions[0].range.clone(), sessions[0].commits), (0..3, 3));
+        assert_eq!((sessions[1].range.clone(), sessions[1].commits), (3..5, 2));
+        // every session gets the first commit bonus
+        assert_eq!(sessions[0].minutes, 30.0 + 90.0);
+        assert_eq!(sessions[1].minutes, 30.0 + 10.0);
+
+        // a gap of exactly the threshold starts a new session
+        let sessions = split_sessions(&config(), 120, &[commit(0, 1.0), commit(120, 1.0)]);
+        assert_eq!(sessions.len(), 2);
+        let sessions = split_sessions(&config(), 121, &[commit(0, 1.0), commit(120, 1.0)]);
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn base_hours_added_once() {
+        let config = EstimatorConfig {
+            base_hours: 10.0,
+            ..config()
+        };
+        let sessions = split_sessions(&config, 120, &[commit(0, 1.0), commit(300, 1.0)]);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(estimate_hours(&config, &sessions), 10.0 + 1.0);
+        assert_eq!(estimate_hours(&config, &[]), 10.0);
+    }
+
+    #[test]
+    fn share_scales_time() {
+        // the first commit is shared by two authors, the second by four
+        let sessions = split_sessions(&config(), 120, &[commit(0, 0.5), commit(60, 0.25)]);
+        assert_eq!(sessions[0].minutes, 0.5 * 30.0 + 0.25 * 60.0);
+    }
+}
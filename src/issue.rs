@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use gix::bstr::BStr;
+use regex::Regex;
+
+use crate::{CommitInfo, EstimatorConfig, Session};
+
+/// The issue key that time is attributed to if no commit of a session references an issue.
+pub const NO_ISSUE: &str = "(none)";
+
+/// The hours of an author spent on a single issue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueHours {
+    /// Key of the issue, e.g. `PROJ-123`.
+    pub key: String,
+    /// Hours spent on the issue.
+    pub hours: f64,
+}
+
+/// Extract the distinct issue keys matched by `pattern` in `text`, in order of appearance.
+///
+/// If the pattern has a capture group, the first group is the key, otherwise the whole match.
+pub fn issue_keys(pattern: &Regex, text: &BStr) -> Vec<String> {
+    let text = text.to_string();
+    let mut keys: Vec<String> = Vec::new();
+    for captures in pattern.captures_iter(&text) {
+        let Some(key) = captures.get(1).or_else(|| captures.get(0)) else {
+            continue;
+        };
+        if !keys.iter().any(|k| k == key.as_str()) {
+            keys.push(key.as_str().to_string());
+        }
+    }
+    keys
+}
+
+/// Split the hours of `sessions` across the issues referenced by their commits.
+///
+/// The time of a session is split evenly across all issues referenced by its commits inside the
+/// configured window, and goes to [`NO_ISSUE`] if there are none. The
+/// [`base_hours`](EstimatorConfig::base_hours) are not attributed to any issue. The result is
+/// sorted by issue key.
+pub fn split_issues(
+    config: &EstimatorConfig,
+    commits: &[CommitInfo],
+    sessions: &[Session],
+) -> Vec<IssueHours> {
+    let mut hours = BTreeMap::new();
+    for session in sessions {
+        let mut keys: Vec<&str> = commits[session.range.clone()]
+            .iter()
+            .filter(|commit| config.contains(commit.time))
+            .flat_map(|commit| &commit.issues)
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let session_hours = session.minutes / 60.0;
+        if keys.is_empty() {
+            *hours.entry(NO_ISSUE.to_string()).or_default() += session_hours;
+        } else {
+            let share = session_hours / keys.len() as f64;
+            for key in keys {
+                *hours.entry(key.to_string()).or_default() += share;
+            }
+        }
+    }
+
+    hours
+        .into_iter()
+        .map(|(key, hours)| IssueHours { key, hours })
+        .collect()
+}
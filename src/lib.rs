@@ -0,0 +1,827 @@
+//! Estimate the hours spent on a git repository from its commit history.
+//!
+//! Commits are grouped by author and sorted by time. Authors are identified as configured by
+//! [`EstimatorConfig::identity`], after mapping them through the repository's `.mailmap` and
+//! [`EstimatorConfig::email_aliases`]. Two subsequent commits of the same author that are less
+//! than [`EstimatorConfig::max_commit_diff`] minutes apart are considered part of the same coding
+//! session, and the time between them is counted as work. The first commit of
+//! every session is credited with [`EstimatorConfig::first_commit_add`] minutes, and every author
+//! is credited with [`EstimatorConfig::base_hours`] on top. With [`SessionThreshold::Auto`], the
+//! maximum time difference is derived for every author from the gaps between all their commits
+//! instead, see [`adaptive_threshold`].
+//!
+//! Co-authors named in `Co-authored-by` trailers are authors of the commit as well, and the
+//! commit is part of each of their sessions. With [`CoAuthors::Split`], the time credited for the
+//! commit, i.e. the time since the previous commit of the session or the first commit bonus, is
+//! divided between them.
+//!
+//! When the estimate is restricted to a time window with [`EstimatorConfig::since`] and
+//! [`EstimatorConfig::until`], sessions that cross a boundary of the window are clipped: only the
+//! part of the time between two commits that lies inside the window is counted, and the first
+//! commit bonus is only credited if the commit that starts the session lies inside the window.
+//! This makes estimates of adjacent windows add up to the estimate of the combined window.
+//!
+//! ```no_run
+//! let repo = gix::open(".")?;
+//! let config = git_hours::EstimatorConfig::default();
+//! for estimate in git_hours::estimate(&config, &repo)?.authors {
+//!     println!("{}: {:.1} hours", estimate.author, estimate.hours);
+//! }
+//! # anyhow::Ok(())
+//! ```
+
+/// Implement [`FromStr`](std::str::FromStr) and [`Display`](std::fmt::Display) for a fieldless
+/// enum by mapping each variant to a name.
+macro_rules! named_enum {
+    ($ty:ident { $($variant:ident => $name:literal),+ $(,)? }) => {
+        impl $ty {
+            /// The names of all variants, as accepted by [`FromStr`](std::str::FromStr).
+            pub const NAMES: &[&str] = &[$($name),+];
+        }
+
+        impl std::fmt::Display for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(match self {
+                    $(Self::$variant => $name,)+
+                })
+            }
+        }
+
+        impl std::str::FromStr for $ty {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> anyhow::Result<Self> {
+                match s {
+                    $($name => Ok(Self::$variant),)+
+                    _ => anyhow::bail!("expected one of: {}", Self::NAMES.join(", ")),
+                }
+            }
+        }
+    };
+}
+
+mod cache;
+mod collect;
+mod component;
+mod date;
+mod diff;
+mod estimate;
+mod issue;
+mod parallel;
+mod period;
+mod scan;
+mod threshold;
+
+use std::collections::HashMap;
+
+use anyhow::{Context, bail};
+use gix::{
+    ObjectId,
+    bstr::{BStr, BString, ByteSlice},
+};
+use jiff::Timestamp;
+use regex::Regex;
+
+pub use cache::clear_cache;
+pub use collect::{AuthorCommits, CommitInfo, get_commit_times_by_author, merge_commits_by_author};
+pub use component::{
+    Component, ComponentHours, ComponentSplit, NO_COMPONENT, OTHER_COMPONENT, split_components,
+};
+pub use date::{ReferenceTimeZone, parse_date};
+pub use estimate::{Session, estimate_hours, split_sessions};
+pub use issue::{IssueHours, NO_ISSUE, issue_keys, split_issues};
+pub use period::{Period, PeriodHours, split_periods};
+pub use scan::find_repositories;
+pub use threshold::{MIN_GAPS, THRESHOLD_RANGE, adaptive_threshold};
+
+/// How commits are grouped into authors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Identity {
+    /// Group by email. Useful if people use different spellings of their name.
+    #[default]
+    Email,
+    /// Group by name. Useful if people use different emails, e.g. rotating noreply addresses.
+    Name,
+    /// Group by both name and email. Useful if several people or bots share the same email.
+    NameEmail,
+}
+
+named_enum!(Identity {
+    Email => "email",
+    Name => "name",
+    NameEmail => "name-email",
+});
+
+/// How merge commits (commits with more than one parent) are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Merges {
+    /// Count merge commits like any other commit.
+    #[default]
+    Include,
+    /// Skip merge commits.
+    Exclude,
+    /// Only count merge commits.
+    Only,
+}
+
+named_enum!(Merges {
+    Include => "include",
+    Exclude => "exclude",
+    Only => "only",
+});
+
+/// Which time of a commit places it on the timeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeSource {
+    /// The time the change was originally made
+    #[default]
+    Author,
+    /// The time the commit was created, which is updated by rebases, amends and `git am`
+    Committer,
+    /// The later of the author and committer time
+    Max,
+    /// The earlier of the author and committer time
+    Min,
+}
+
+named_enum!(TimeSource {
+    Author => "author",
+    Committer => "committer",
+    Max => "max",
+    Min => "min",
+});
+
+impl TimeSource {
+    /// Pick the time of a commit with the `author` and `committer` times.
+    pub fn pick(self, author: gix::date::Time, committer: gix::date::Time) -> gix::date::Time {
+        match self {
+            TimeSource::Author => author,
+            TimeSource::Committer => committer,
+            TimeSource::Max => author.max(committer),
+            TimeSource::Min => author.min(committer),
+        }
+    }
+}
+
+/// How the maximum gap between two commits of a session is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionThreshold {
+    /// Use [`EstimatorConfig::max_commit_diff`] for every author
+    #[default]
+    Fixed,
+    /// Derive it for every author from the gaps between their commits, see
+    /// [`adaptive_threshold`]. Authors with too few commits use
+    /// [`EstimatorConfig::max_commit_diff`]. The gaps are taken from the whole history, not only
+    /// the [`since`](EstimatorConfig::since)..[`until`](EstimatorConfig::until) window, so an
+    /// author gets the same threshold in every window.
+    Auto,
+}
+
+named_enum!(SessionThreshold {
+    Fixed => "fixed",
+    Auto => "auto",
+});
+
+/// How authors named in `Co-authored-by` trailers are credited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoAuthors {
+    /// Credit every co-author as if they had made the commit alone
+    #[default]
+    CreditAll,
+    /// Divide the time credited for a commit between its author and co-authors
+    Split,
+    /// Only credit the author
+    Ignore,
+}
+
+named_enum!(CoAuthors {
+    CreditAll => "credit-all",
+    Split => "split",
+    Ignore => "ignore",
+});
+
+impl Merges {
+    /// Whether a commit with `num_parents` parents is counted.
+    pub fn includes(self, num_parents: usize) -> bool {
+        let is_merge = num_parents > 1;
+        match self {
+            Merges::Include => true,
+            Merges::Exclude => !is_merge,
+            Merges::Only => is_merge,
+        }
+    }
+}
+
+/// Names or emails of well-known bots that don't follow the `[bot]` convention.
+const BOT_NAMES: &[&str] = &[
+    "dependabot",
+    "renovate",
+    "greenkeeper",
+    "snyk-bot",
+    "github-actions",
+    "pre-commit-ci",
+    "allcontributors",
+    "imgbot",
+    "mergify",
+];
+
+/// Heuristic whether the author with `name` and `email` is a bot: GitHub apps whose names end in
+/// `[bot]` and whose emails end in `[bot]@users.noreply.github.com`, and a list of well-known
+/// bots like dependabot and renovate.
+pub fn is_bot(name: &BStr, email: &BStr) -> bool {
+    let name = name.to_str_lossy().to_lowercase();
+    let email = email.to_str_lossy().to_lowercase();
+    name.ends_with("[bot]")
+        || email.ends_with("[bot]@users.noreply.github.com")
+        || BOT_NAMES
+            .iter()
+            .any(|bot| name.contains(bot) || email.contains(bot))
+}
+
+/// Configuration of the estimator, independent of how it was obtained.
+#[derive(Debug, Clone)]
+pub struct EstimatorConfig {
+    /// Maximum time difference between two subsequent commits in minutes which are counted to be
+    /// in the same coding session
+    pub max_commit_diff: u32,
+    /// How the maximum time difference between commits of a session is chosen for every author
+    pub threshold: SessionThreshold,
+    /// How many minutes should be added for the first commit of each coding session
+    pub first_commit_add: u32,
+    /// How many hours should be added for every author
+    pub base_hours: f64,
+    /// Hourly rate the estimated hours of authors without an [author rate](Self::author_rates)
+    /// are billed at, see [`AuthorEstimate::cost`]
+    pub rate: Option<f64>,
+    /// Hourly rates of individual authors, by the key their commits are grouped by, see
+    /// [`Identity`]
+    pub author_rates: HashMap<BString, f64>,
+    /// How merge commits are handled
+    pub merges: Merges,
+    /// Only follow the first parent of each commit, i.e. only walk the mainline history
+    pub first_parent: bool,
+    /// Revisions to walk, in any form understood by `git rev-parse`, including ranges like
+    /// `v1.0..v2.0` and exclusions like `^main`. All local branches are walked if neither these
+    /// nor [`Self::all`], [`Self::remotes`] or [`Self::tags`] select a commit to start from.
+    pub revisions: Vec<String>,
+    /// Walk all references and `HEAD`
+    pub all: bool,
+    /// Walk all remote-tracking branches
+    pub remotes: bool,
+    /// Walk all tags
+    pub tags: bool,
+    /// Only count work done at or after this time
+    pub since: Option<Timestamp>,
+    /// Only count work done before this time
+    pub until: Option<Timestamp>,
+    /// How commits are grouped into authors
+    pub identity: Identity,
+    /// Map author identities through the repository's mailmap
+    pub mailmap: bool,
+    /// Only count authors matching any of these patterns. Like for `git log --author`, the
+    /// patterns are matched against `Name <email>`, after the mailmap and aliases are applied.
+    pub authors: Vec<Regex>,
+    /// Don't count authors matching any of these patterns, see [`Self::authors`]
+    pub exclude_authors: Vec<Regex>,
+    /// How co-authors named in `Co-authored-by` trailers are credited
+    pub co_authors: CoAuthors,
+    /// Which time of a commit places it on the timeline
+    pub timestamp: TimeSource,
+    /// How many threads diff commits and estimate authors in parallel. `0` uses one thread per
+    /// available CPU.
+    pub threads: usize,
+    /// Only count the earliest of an author's commits that make the same changes, like
+    /// cherry-picks and rebased copies on other branches. Commits are compared by a patch id
+    /// similar to `git patch-id --stable`, which requires diffing every commit.
+    pub dedupe_patches: bool,
+    /// Keep per-commit data like authors and changed files in a cache under
+    /// `<git dir>/git-hours/`, so later runs don't decode and diff the same commits again. See
+    /// [`clear_cache`].
+    pub cache: bool,
+    /// Report commits whose author and committer times are more than this many minutes apart,
+    /// which is a sign of rewritten history, see [`Report::rewritten_commits`]
+    pub rewrite_threshold: u32,
+    /// Don't count bots like dependabot, renovate or GitHub apps, see [`is_bot`]
+    pub exclude_bots: bool,
+    /// Only count commits whose message matches any of these patterns
+    pub grep: Vec<Regex>,
+    /// Only count commits whose message matches none of [`Self::grep`] instead
+    pub invert_grep: bool,
+    /// Attribute hours to the issues whose keys this pattern extracts from commit messages, see
+    /// [`issue_keys`]. Commits without a key in their message get the keys in the names of the
+    /// branches they are exclusively part of.
+    pub issue_pattern: Option<Regex>,
+    /// Split the hours of every author into calendar periods
+    pub group_by: Option<Period>,
+    /// Only count commits whose changes compared to their first parent touch a path matching
+    /// one of these pathspecs, in the syntax of `git log -- <pathspec>`. Paths are relative to
+    /// the repository root.
+    pub pathspecs: Vec<String>,
+    /// Ignore changes to paths matching any of these globs, e.g. `Cargo.lock` or `vendor/**`.
+    /// Commits that only change such paths are not counted.
+    pub exclude_paths: Vec<String>,
+    /// Named components to attribute hours to. The first component matching a changed file wins.
+    pub components: Vec<Component>,
+    /// Attribute hours to the directories up to this depth. Applies to changed files that don't
+    /// match any of [`Self::components`].
+    pub path_depth: Option<usize>,
+    /// How the time of a session is split across components
+    pub component_split: ComponentSplit,
+    /// Time zone for calendar computations, e.g. [`Self::group_by`], and for displaying times
+    pub time_zone: ReferenceTimeZone,
+    /// Estimate shallow repositories over the available history instead of failing
+    pub allow_shallow: bool,
+    /// Estimate the checked out submodules along with the repository, recursively. Submodules
+    /// are walked from their checked out commit instead of the configured revisions, and the
+    /// paths they change are prefixed with their path, so [components](Self::components) and
+    /// [pathspecs](Self::pathspecs) apply to them as part of the repository.
+    pub recurse_submodules: bool,
+    /// Aliases of emails for grouping the same activity as one person. Aliases are applied after
+    /// the mailmap.
+    pub email_aliases: HashMap<BString, BString>,
+}
+
+impl Default for EstimatorConfig {
+    fn default() -> Self {
+        Self {
+            max_commit_diff: 2 * 60,
+            threshold: SessionThreshold::default(),
+            first_commit_add: 2 * 60,
+            base_hours: 0.0,
+            rate: None,
+            author_rates: HashMap::new(),
+            merges: Merges::default(),
+            first_parent: false,
+            revisions: Vec::new(),
+            all: false,
+            remotes: false,
+            tags: false,
+            since: None,
+            until: None,
+            identity: Identity::default(),
+            mailmap: true,
+            group_by: None,
+            pathspecs: Vec::new(),
+            exclude_paths: Vec::new(),
+            components: Vec::new(),
+            path_depth: None,
+            component_split: ComponentSplit::default(),
+            time_zone: ReferenceTimeZone::default(),
+            allow_shallow: false,
+            recurse_submodules: false,
+            email_aliases: HashMap::new(),
+            authors: Vec::new(),
+            exclude_authors: Vec::new(),
+            co_authors: CoAuthors::default(),
+            timestamp: TimeSource::default(),
+            threads: 0,
+            dedupe_patches: false,
+            cache: false,
+            rewrite_threshold: 24 * 60,
+            exclude_bots: false,
+            grep: Vec::new(),
+            invert_grep: false,
+            issue_pattern: None,
+        }
+    }
+}
+
+impl EstimatorConfig {
+    /// Whether `time` lies inside the window given by [`Self::since`] and [`Self::until`].
+    pub fn contains(&self, time: gix::date::Time) -> bool {
+        self.since
+            .is_none_or(|since| time.seconds >= since.as_second())
+            && self
+                .until
+                .is_none_or(|until| time.seconds < until.as_second())
+    }
+
+    /// Resolve `email` through [`Self::email_aliases`].
+    pub fn resolve_alias<'a>(&'a self, email: &'a BStr) -> &'a BStr {
+        self.email_aliases
+            .get(email)
+            .map_or(email, |alias| alias.as_ref())
+    }
+
+    /// Whether the author with `name` and `email` is counted, considering [`Self::authors`],
+    /// [`Self::exclude_authors`] and [`Self::exclude_bots`].
+    pub fn includes_author(&self, name: &BStr, email: &BStr) -> bool {
+        let identity = format!("{name} <{email}>");
+        (self.authors.is_empty() || self.authors.iter().any(|re| re.is_match(&identity)))
+            && !self.exclude_authors.iter().any(|re| re.is_match(&identity))
+            && !(self.exclude_bots && is_bot(name, email))
+    }
+
+    /// Whether a commit with `message` is counted, considering [`Self::grep`] and
+    /// [`Self::invert_grep`].
+    pub fn includes_message(&self, message: &BStr) -> bool {
+        if self.grep.is_empty() {
+            return true;
+        }
+        let message = message.to_str_lossy();
+        self.grep.iter().any(|re| re.is_match(&message)) != self.invert_grep
+    }
+
+    /// The number of threads to use, resolving [`Self::threads`].
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => parallel::default_threads(),
+            threads => threads,
+        }
+    }
+
+    /// The hourly rate of `author`, given by the key their commits are grouped by.
+    pub fn rate_of(&self, author: &BStr) -> Option<f64> {
+        self.author_rates.get(author).copied().or(self.rate)
+    }
+
+    /// Whether hours are attributed to components, which requires diffing every commit.
+    pub fn has_components(&self) -> bool {
+        !self.components.is_empty() || self.path_depth.is_some()
+    }
+
+    /// Whether commits are filtered by the paths they change, see [`Self::pathspecs`] and
+    /// [`Self::exclude_paths`].
+    pub fn has_path_filter(&self) -> bool {
+        !self.pathspecs.is_empty() || !self.exclude_paths.is_empty()
+    }
+
+    /// Whether changes to `path` are ignored because of [`Self::exclude_paths`].
+    pub fn is_path_excluded(&self, path: &BStr) -> bool {
+        self.exclude_paths.iter().any(|glob| {
+            gix::glob::wildmatch(
+                glob.as_bytes().as_bstr(),
+                path,
+                gix::glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+            )
+        })
+    }
+
+    /// The component a changed file at `path` belongs to. The first matching
+    /// [`Self::components`] wins, then [`Self::path_depth`] applies.
+    pub fn component_of(&self, path: &BStr) -> String {
+        if let Some(component) = self.components.iter().find(|c| c.matches(path)) {
+            return component.name.clone();
+        }
+        let Some(depth) = self.path_depth else {
+            return OTHER_COMPONENT.to_string();
+        };
+
+        // only directories make up components, files at the root belong to `.`
+        let mut dirs: Vec<_> = path.split_str("/").collect();
+        dirs.pop();
+        dirs.truncate(depth);
+        if dirs.is_empty() {
+            ".".to_string()
+        } else {
+            dirs.join(&b'/').to_str_lossy().into_owned()
+        }
+    }
+
+    /// Clip the interval between the unix times `start` and `end` to the window given by
+    /// [`Self::since`] and [`Self::until`]. The result is empty, i.e. `end <= start`, if the
+    /// interval lies outside of the window.
+    fn clip(&self, start: i64, end: i64) -> (i64, i64) {
+        let start = self
+            .since
+            .map_or(start, |since| start.max(since.as_second()));
+        let end = self.until.map_or(end, |until| end.min(until.as_second()));
+        (start, end)
+    }
+
+    /// Like [`Self::clip`], but return the remaining length in seconds.
+    fn clipped_seconds(&self, start: i64, end: i64) -> i64 {
+        let (start, end) = self.clip(start, end);
+        (end - start).max(0)
+    }
+}
+
+/// The estimate for a single author.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorEstimate {
+    /// The key the author's commits are grouped by, see [`Identity`].
+    pub author: BString,
+    /// The display name of the author.
+    pub name: BString,
+    /// Number of commits attributed to the author.
+    pub commits: usize,
+    /// Estimated hours.
+    pub hours: f64,
+    /// The maximum time difference between commits of a session in minutes the author's commits
+    /// were split with, which is derived from them with [`SessionThreshold::Auto`].
+    pub threshold: u32,
+    /// The estimated hours billed at the author's rate. `None` if no
+    /// [rate](EstimatorConfig::rate) applies to the author.
+    pub cost: Option<f64>,
+    /// The coding sessions of the author.
+    pub sessions: Vec<Session>,
+    /// Time of the author's first commit inside the configured window. `None` if the author is
+    /// only credited for the part of a session before their next commit after the window.
+    pub first_commit: Option<gix::date::Time>,
+    /// Time of the author's last commit inside the configured window, `None` like
+    /// [`Self::first_commit`].
+    pub last_commit: Option<gix::date::Time>,
+    /// The hours of the author split by [`EstimatorConfig::group_by`], sorted by period. Empty if
+    /// hours are not grouped.
+    pub periods: Vec<PeriodHours>,
+    /// The hours of the author split across components, sorted by component name. Empty if no
+    /// components are configured.
+    pub components: Vec<ComponentHours>,
+    /// The hours of the author split across issues, sorted by issue key. Empty if no
+    /// [`issue_pattern`](EstimatorConfig::issue_pattern) is configured.
+    pub issues: Vec<IssueHours>,
+}
+
+/// The result of estimating the hours of a repository.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// The estimates of all authors, sorted by estimated hours, ascending.
+    pub authors: Vec<AuthorEstimate>,
+    /// The commits at which the history of a shallow repository is truncated. Their parents are
+    /// missing, so work before them is not part of the estimate. Empty if the repository is not
+    /// shallow.
+    pub shallow_commits: Vec<ObjectId>,
+    /// The counted commits whose author and committer times are more than
+    /// [`EstimatorConfig::rewrite_threshold`] minutes apart, e.g. because they were rebased or
+    /// applied from patches. Their times may not reflect when the work was done.
+    pub rewritten_commits: Vec<ObjectId>,
+    /// Problems that didn't prevent the estimate, like a failure to write the
+    /// [cache](EstimatorConfig::cache).
+    pub warnings: Vec<String>,
+}
+
+/// Estimate the hours of a single `author` from their `commits`. Returns `None` if none of their
+/// sessions overlaps the configured window.
+fn estimate_author(
+    config: &EstimatorConfig,
+    author: &BString,
+    AuthorCommits { name, commits }: &AuthorCommits,
+) -> anyhow::Result<Option<AuthorEstimate>> {
+    let threshold = match config.threshold {
+        SessionThreshold::Fixed => config.max_commit_diff,
+        SessionThreshold::Auto => adaptive_threshold(commits).unwrap_or(config.max_commit_diff),
+    };
+
+    let sessions = split_sessions(config, threshold, commits);
+    if sessions.is_empty() {
+        return Ok(None);
+    }
+    let mut in_window = commits.iter().filter(|commit| config.contains(commit.time));
+    let first_commit = in_window.next().map(|commit| commit.time);
+    let last_commit = in_window
+        .next_back()
+        .map(|commit| commit.time)
+        .or(first_commit);
+    let periods = match config.group_by {
+        Some(period) => split_periods(config, period, &config.time_zone, commits, &sessions)?,
+        None => Vec::new(),
+    };
+    let components = if config.has_components() {
+        split_components(config, commits, &sessions)
+    } else {
+        Vec::new()
+    };
+    let issues = if config.issue_pattern.is_some() {
+        split_issues(config, commits, &sessions)
+    } else {
+        Vec::new()
+    };
+    let hours = estimate_hours(config, &sessions);
+    Ok(Some(AuthorEstimate {
+        author: author.clone(),
+        name: name.clone(),
+        commits: commits
+            .iter()
+            .filter(|commit| config.contains(commit.time))
+            .count(),
+        hours,
+        threshold,
+        cost: config.rate_of(author.as_ref()).map(|rate| rate * hours),
+        sessions,
+        first_commit,
+        last_commit,
+        periods,
+        components,
+        issues,
+    }))
+}
+
+/// Collect the commits of `repo` and estimate the hours of every author.
+///
+/// Only commits inside the configured window are counted, and authors without any session
+/// overlapping it are omitted. A session crossing the window is credited with the part inside
+/// it, even if none of its commits are.
+///
+/// Fails for shallow repositories unless [`EstimatorConfig::allow_shallow`] is set, as their
+/// history is incomplete.
+pub fn estimate(config: &EstimatorConfig, repo: &gix::Repository) -> anyhow::Result<Report> {
+    estimate_repositories(config, std::slice::from_ref(repo))
+}
+
+/// Collect the commits of all `repos` and estimate the hours of every author across them, like
+/// [`estimate`] does for a single repository.
+///
+/// The commits of an author are merged into a single timeline before splitting them into
+/// sessions, see [`merge_commits_by_author`], so switching between repositories within a session
+/// isn't counted twice. The configured revisions are resolved in every repository.
+///
+/// With [`EstimatorConfig::recurse_submodules`], the checked out submodules of the repositories
+/// are estimated along with them.
+pub fn estimate_repositories(
+    config: &EstimatorConfig,
+    repos: &[gix::Repository],
+) -> anyhow::Result<Report> {
+    let submodule_config = EstimatorConfig {
+        revisions: vec!["HEAD".into()],
+        all: false,
+        remotes: false,
+        tags: false,
+        ..config.clone()
+    };
+    // every repository with the path it is nested at, and the config to collect it with
+    let mut sources = Vec::with_capacity(repos.len());
+    for repo in repos {
+        sources.push((repo.clone(), BString::default(), config));
+        if config.recurse_submodules {
+            for (path, submodule) in collect::submodules(repo)? {
+                sources.push((submodule, path, &submodule_config));
+            }
+        }
+    }
+
+    let mut shallow_commits = Vec::new();
+    let mut warnings = Vec::new();
+    let mut commits_by_author = Vec::with_capacity(sources.len());
+    for (repo, prefix, config) in &sources {
+        let mut collect = || -> anyhow::Result<_> {
+            let shallow = repo
+                .shallow_commits()?
+                .map(|commits| commits.to_vec())
+                .unwrap_or_default();
+            if !shallow.is_empty() && !config.allow_shallow {
+                bail!(
+                    "Cannot analyze shallow copies. Please run `git fetch --unshallow` before continuing."
+                );
+            }
+            Ok((
+                shallow,
+                collect::collect_commits(config, repo, prefix.as_ref(), &mut warnings)?,
+            ))
+        };
+        let (shallow, commits) = match sources.len() {
+            1 => collect()?,
+            _ => collect().with_context(|| {
+                let path = repo.workdir().unwrap_or(repo.path());
+                format!("failed to estimate repository {}", path.display())
+            })?,
+        };
+        shallow_commits.extend(shallow);
+        commits_by_author.push(commits);
+    }
+
+    let authors: Vec<_> = merge_commits_by_author(config, commits_by_author)
+        .into_iter()
+        .collect();
+    let mut rewritten_commits: Vec<_> = authors
+        .iter()
+        .flat_map(|(_, author)| &author.commits)
+        .filter(|commit| commit.rewritten && config.contains(commit.time))
+        .map(|commit| commit.id)
+        .collect();
+    let estimates = parallel::map(
+        config.threads(),
+        &authors,
+        || (),
+        |_, (author, commits)| estimate_author(config, author, commits),
+    );
+    let mut authors = Vec::new();
+    for estimate in estimates {
+        authors.extend(estimate?);
+    }
+
+    authors.sort_by(|a, b| {
+        a.hours
+            .total_cmp(&b.hours)
+            .then_with(|| a.author.cmp(&b.author))
+    });
+
+    // commits with co-authors are part of several timelines
+    rewritten_commits.sort_unstable();
+    rewritten_commits.dedup();
+
+    Ok(Report {
+        authors,
+        shallow_commits,
+        rewritten_commits,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(since: Option<i64>, until: Option<i64>) -> EstimatorConfig {
+        EstimatorConfig {
+            since: since.map(|since| Timestamp::from_second(since).unwrap()),
+            until: until.map(|until| Timestamp::from_second(until).unwrap()),
+            ..EstimatorConfig::default()
+        }
+    }
+
+    #[test]
+    fn clip_to_window() {
+        let config = window(Some(100), Some(200));
+        assert_eq!(config.clipped_seconds(120, 180), 60);
+        assert_eq!(config.clipped_seconds(50, 150), 50);
+        assert_eq!(config.clipped_seconds(150, 250), 50);
+        assert_eq!(config.clipped_seconds(50, 250), 100);
+        // outside of the window
+        assert_eq!(config.clipped_seconds(0, 50), 0);
+        assert_eq!(config.clipped_seconds(250, 300), 0);
+        assert_eq!(config.clipped_seconds(0, 100), 0);
+        assert_eq!(config.clipped_seconds(200, 300), 0);
+    }
+
+    #[test]
+    fn clip_to_open_window() {
+        assert_eq!(window(None, None).clipped_seconds(50, 250), 200);
+        assert_eq!(window(Some(100), None).clipped_seconds(50, 250), 150);
+        assert_eq!(window(None, Some(200)).clipped_seconds(50, 250), 150);
+    }
+
+    #[test]
+    fn adjacent_windows_add_up() {
+        let (earlier, later) = (window(None, Some(150)), window(Some(150), None));
+        for (start, end) in [(50, 250), (100, 150), (150, 200), (0, 10)] {
+            assert_eq!(
+                earlier.clipped_seconds(start, end) + later.clipped_seconds(start, end),
+                window(None, None).clipped_seconds(start, end)
+            );
+        }
+    }
+
+    #[test]
+    fn session_split_across_windows() {
+        let commit = |seconds| CommitInfo {
+            id: ObjectId::null(gix::hash::Kind::Sha1),
+            time: gix::date::Time::new(seconds, 0),
+            rewritten: false,
+            share: 1.0,
+            components: vec![],
+            patch_id: None,
+            issues: vec![],
+        };
+        // 10:00 and 10:30
+        let commits = AuthorCommits {
+            name: "Alice".into(),
+            commits: vec![commit(36_000), commit(37_800)],
+        };
+        let hours = |since, until| {
+            estimate_author(&window(since, until), &"alice".into(), &commits)
+                .unwrap()
+                .map(|estimate| estimate.hours)
+        };
+
+        let windows = [
+            hours(None, Some(36_600)),
+            hours(Some(36_600), Some(37_200)),
+            hours(Some(37_200), Some(43_200)),
+        ];
+        assert_eq!(windows[1], Some(10.0 / 60.0));
+        let total: f64 = windows.into_iter().flatten().sum();
+        assert!((total - hours(None, Some(43_200)).unwrap()).abs() < 1e-9);
+        // no session overlaps the window
+        assert_eq!(hours(Some(43_200), None), None);
+    }
+
+    #[test]
+    fn bots() {
+        let bot = |name: &str, email: &str| is_bot(name.into(), email.into());
+        assert!(bot(
+            "dependabot[bot]",
+            "49699333+dependabot[bot]@users.noreply.github.com"
+        ));
+        assert!(bot("github-actions[bot]", "actions@github.com"));
+        assert!(bot(
+            "Some App",
+            "123+some-app[bot]@users.noreply.github.com"
+        ));
+        assert!(bot("Renovate Bot", "bot@renovateapp.com"));
+        assert!(bot("renovate", "renovate@example.com"));
+        assert!(bot("Dependabot", "support@github.com"));
+        // people with a private GitHub email address
+        assert!(!bot("Jane Doe", "1234+jane@users.noreply.github.com"));
+        assert!(!bot("Jane Doe", "jane@example.com"));
+    }
+
+    #[test]
+    fn window_contains() {
+        let config = window(Some(100), Some(200));
+        let time = |seconds| gix::date::Time::new(seconds, 0);
+        assert!(!config.contains(time(99)));
+        assert!(config.contains(time(100)));
+        assert!(config.contains(time(199)));
+        assert!(!config.contains(time(200)));
+    }
+}
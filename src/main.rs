@@ -1,151 +1,400 @@
-use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
-};
+mod config;
+mod output;
+
+use std::{ffi::OsString, path::PathBuf};
 
-use anyhow::bail;
-use clap::Parser;
-use gix::bstr::BString;
+use anyhow::{Context, bail};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use git_hours::{
+    CoAuthors, Component, ComponentSplit, EstimatorConfig, Identity, Merges, Period,
+    ReferenceTimeZone, SessionThreshold, TimeSource,
+};
+use jiff::{Timestamp, Zoned};
+use output::{Format, Options};
+use regex::Regex;
 
 /// Estimate hours of a project
 #[derive(Debug, Parser, Clone)]
-#[command(version, long_about = None)]
+#[command(version, long_about = None, after_long_help = config::HELP, args_override_self = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Maximum time difference between two subsequent commits in minutes which are counted to be
     /// in the same coding session
     #[arg(short = 'd', long, default_value_t = 2 * 60)]
     max_commit_diff: u32,
 
+    /// How the maximum time difference between commits of a session is chosen: `fixed` uses
+    /// `--max-commit-diff` for everyone, `auto` derives it for every author from the gaps between
+    /// their commits and reports it. Authors with few commits use `--max-commit-diff`
+    #[arg(long, default_value_t, value_parser = named::<SessionThreshold>(SessionThreshold::NAMES))]
+    threshold: SessionThreshold,
+
     /// How many minutes should be added for the first commit of each coding session
     #[arg(short, long, default_value_t = 2 * 60)]
     first_commit_add: u32,
 
-    // /// Include commits since
-    // #[arg(short, long)]
-    // since:,
-    // /// Include commits until
-    // #[arg(short, long)]
-    // until:,
-
-    // TODO: consider making flag instead of value
-    /// Include merge commits (commits with more than one parent)
-    #[arg(short, long, default_value_t = true)]
-    merge_commits: bool,
-
-    /// Git repository
-    #[arg(short, long, default_value = ".")]
-    path: PathBuf,
-
-    // Aliases of emails for grouping the same activity as one person
-    // #[arg(short, long)]
-    // email_aliases: HashMap<String, String>,
-    /// Git branch
+    /// How many hours should be added for every author
+    #[arg(long, default_value_t = 0.0)]
+    base_hours: f64,
+
+    /// Hourly rate to bill the estimated hours at, reported as the cost of every author
+    #[arg(long, value_name = "AMOUNT")]
+    rate: Option<f64>,
+
+    /// Hourly rate of a single author, given by the key their commits are grouped by, e.g.
+    /// `jane@example.com=95`. Overrides `--rate`. Can be given multiple times
+    #[arg(long = "author-rate", value_name = "AUTHOR=AMOUNT", value_parser = parse_author_rate)]
+    author_rates: Vec<(String, f64)>,
+
+    /// Only count work done since this date. Accepts dates (`2024-03-01`), RFC 3339 timestamps
+    /// and relative dates (`2 weeks ago`, `yesterday`, `last monday`)
+    #[arg(short, long)]
+    since: Option<String>,
+
+    /// Only count work done before this date. Accepts the same formats as `--since`
+    #[arg(short, long)]
+    until: Option<String>,
+
+    /// Time zone for day boundaries and printed times: `commit` for the local time of each
+    /// commit's author, `utc`, or an IANA time zone name like `Europe/Berlin`. Dates given to
+    /// `--since` and `--until` are interpreted in the system time zone for `commit`
+    #[arg(long, default_value_t)]
+    tz: ReferenceTimeZone,
+
+    /// Which time of a commit places it on the timeline: `author`, `committer` (updated by
+    /// rebases and `git am`), or the later (`max`) or earlier (`min`) of both
+    #[arg(long, default_value_t, value_parser = named::<TimeSource>(TimeSource::NAMES))]
+    timestamp: TimeSource,
+
+    /// Only count the earliest of an author's commits with the same changes, like cherry-picks
+    /// and rebased copies of commits on other branches
+    #[arg(long)]
+    dedupe_patches: bool,
+
+    /// How many threads diff commits and estimate authors in parallel. Defaults to one thread
+    /// per CPU
+    #[arg(short = 'j', long, default_value_t = 0, hide_default_value = true)]
+    threads: usize,
+
+    /// Don't read or write the cache of per-commit data under `.git/git-hours/`
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Warn about commits whose author and committer times are more than this many minutes
+    /// apart, which is a sign of rewritten history
+    #[arg(long, default_value_t = 24 * 60, value_name = "MINUTES")]
+    rewrite_threshold: u32,
+
+    /// How merge commits (commits with more than one parent) are handled
+    #[arg(short, long, default_value_t, value_parser = named::<Merges>(Merges::NAMES))]
+    merges: Merges,
+
+    /// Only follow the first parent of merge commits
+    #[arg(long)]
+    first_parent: bool,
+
+    /// Git repository. Can be given multiple times to estimate several repositories together,
+    /// with the commits of every author merged into a single timeline. Defaults to the current
+    /// directory unless `--scan` is given
+    #[arg(short, long, global = true)]
+    path: Vec<PathBuf>,
+
+    /// Estimate all git repositories found in this directory and its subdirectories together,
+    /// like repositories given to `--path`. Can be given multiple times
+    #[arg(long, value_name = "DIR", global = true)]
+    scan: Vec<PathBuf>,
+
+    /// Aliases of emails for grouping the same activity as one person, in the form `old=new`.
+    /// Can be given multiple times
+    #[arg(short, long = "alias", value_name = "OLD=NEW", value_parser = parse_alias)]
+    email_aliases: Vec<(String, String)>,
+
+    /// How commits are grouped into authors
+    #[arg(long, default_value_t, value_parser = named::<Identity>(Identity::NAMES))]
+    identity: Identity,
+
+    /// Only count authors matching this regular expression. Like for `git log --author`, it is
+    /// matched against `Name <email>`. Can be given multiple times to match any of them
+    #[arg(long = "author", value_name = "REGEX")]
+    authors: Vec<Regex>,
+
+    /// Don't count authors matching this regular expression. Can be given multiple times
+    #[arg(long = "exclude-author", value_name = "REGEX")]
+    exclude_authors: Vec<Regex>,
+
+    /// How authors named in `Co-authored-by` trailers are credited: `credit-all` credits each of
+    /// them with the full time, `split` divides the time between the author and co-authors
+    #[arg(long, default_value_t, value_parser = named::<CoAuthors>(CoAuthors::NAMES))]
+    co_authors: CoAuthors,
+
+    /// Don't count bots like dependabot, renovate and GitHub apps (`[bot]` accounts)
+    #[arg(long)]
+    exclude_bots: bool,
+
+    /// Only count commits whose message matches this regular expression. Can be given multiple
+    /// times to match any of them
+    #[arg(long, value_name = "REGEX")]
+    grep: Vec<Regex>,
+
+    /// Only count commits whose message doesn't match any `--grep`
+    #[arg(long, requires = "grep")]
+    invert_grep: bool,
+
+    /// Attribute hours to the issues whose keys this regular expression extracts from commit
+    /// messages, e.g. `[A-Z]+-\d+` for Jira. Commits without a key in their message get the keys
+    /// in the name of the branch they are exclusively part of
+    #[arg(long, value_name = "REGEX")]
+    issue_pattern: Option<Regex>,
+
+    /// Estimate shallow clones over the available history instead of failing. The commits at
+    /// which the history is truncated are reported
+    #[arg(long)]
+    allow_shallow: bool,
+
+    /// Also estimate the checked out submodules, recursively, from the commit they have checked
+    /// out. Paths they change are prefixed with the submodule path, so `--component` and
+    /// `--by-path-depth` can break the hours down per submodule
+    #[arg(long)]
+    recurse_submodules: bool,
+
+    /// Don't map authors through the repository's `.mailmap`
+    #[arg(long)]
+    no_mailmap: bool,
+
+    /// Revisions to walk, e.g. `main`, `origin/main`, `v1.0..v2.0`, `HEAD~50..` or `^excluded`.
+    /// All local branches are walked if no revision is given
+    #[arg(value_name = "REVISION")]
+    revisions: Vec<String>,
+
+    /// Only count commits touching paths matching these pathspecs, given after `--` like for
+    /// `git log`
+    #[arg(last = true, value_name = "PATHSPEC")]
+    pathspecs: Vec<String>,
+
+    /// Ignore changes to paths matching this glob, e.g. `Cargo.lock` or `vendor/**`. Commits
+    /// only changing such paths are not counted. Can be given multiple times
+    #[arg(long = "exclude-path", value_name = "GLOB")]
+    exclude_paths: Vec<String>,
+
+    /// Git branch. Can be given multiple times
     #[arg(short, long)]
-    branch: Option<String>,
+    branch: Vec<String>,
+
+    /// Walk all references and `HEAD`
+    #[arg(long)]
+    all: bool,
+
+    /// Walk all remote-tracking branches
+    #[arg(long)]
+    remotes: bool,
+
+    /// Walk all tags
+    #[arg(long)]
+    tags: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+
+    /// Split the hours of every author into calendar periods
+    #[arg(long, value_parser = named::<Period>(Period::NAMES))]
+    group_by: Option<Period>,
+
+    /// Attribute hours to a named component given by a glob over repository relative paths, in
+    /// the form `name=glob`, e.g. `backend=backend/**`. Can be given multiple times
+    #[arg(long = "component", value_name = "NAME=GLOB")]
+    components: Vec<Component>,
+
+    /// Attribute hours to the directories up to this depth, for files that don't match any
+    /// `--component`
+    #[arg(long = "by-path-depth", value_name = "N")]
+    path_depth: Option<usize>,
+
+    /// How the time of a session is split across the components its commits changed
+    #[arg(long, default_value_t, value_parser = named::<ComponentSplit>(ComponentSplit::NAMES))]
+    component_split: ComponentSplit,
+
+    /// List the coding sessions of every author that make up the estimate
+    #[arg(long)]
+    sessions: bool,
 }
 
-fn get_commit_times_by_author(
-    args: &Args,
-    repo: &gix::Repository,
-) -> anyhow::Result<HashMap<BString, Vec<gix::date::Time>>> {
-    let refs = repo.references()?;
-    let prefix = if let Some(branch) = &args.branch {
-        format!("refs/heads/{branch}")
-    } else {
-        "refs/heads/".to_string()
-    };
-    let heads = refs.prefixed(prefix.as_str())?;
-
-    let mut visited = HashSet::new();
-    let mut times_by_author: HashMap<BString, Vec<gix::date::Time>> = HashMap::new();
-    for head in heads.filter_map(|h| h.ok()) {
-        let mut stack = vec![head.id()];
-        while let Some(id) = stack.pop() {
-            let Ok(commit) = repo.find_commit(id) else {
-                continue;
-            };
-
-            if visited.contains(&commit.id) {
-                // This commit and its parents have already been visited. Any further work is
-                // redundant.
-                continue;
-            }
-            visited.insert(commit.id);
-
-            let stack_len = stack.len();
-            // extend the stack directly to avoid allocating a temporary vec for the parents.
-            stack.extend(commit.parent_ids());
-            let num_parents = stack.len() - stack_len;
-
-            if let Ok(author) = commit.author()
-                && let Ok(time) = author.time()
-            {
-                let is_merge = num_parents > 1;
-                if !is_merge || args.merge_commits {
-                    // TODO:
-                    // - filter by since/until
-                    // - consider using name instead of email (or both?) (or configurable?)
-                    // - email/name aliases
-                    if let Some(times) = times_by_author.get_mut(author.email) {
-                        times.push(time);
-                    } else {
-                        times_by_author.insert(author.email.into(), vec![time]);
-                    }
-                }
+#[derive(Debug, clap::Subcommand, Clone)]
+enum Command {
+    /// Manage the cache of per-commit data
+    #[command(subcommand)]
+    Cache(CacheCommand),
+}
+
+#[derive(Debug, clap::Subcommand, Clone)]
+enum CacheCommand {
+    /// Delete the cache of the repository
+    Clear,
+}
+
+impl Args {
+    /// Open the repositories given by `--path` and found by `--scan`. Repositories found more
+    /// than once are only opened once. Directories skipped by `--scan` are added to `warnings`.
+    fn repositories(&self, warnings: &mut Vec<String>) -> anyhow::Result<Vec<gix::Repository>> {
+        let mut paths = self.path.clone();
+        for dir in &self.scan {
+            let found = git_hours::find_repositories(dir, warnings)?;
+            if found.is_empty() {
+                bail!("no git repository found in {}", dir.display());
             }
+            paths.extend(found);
+        }
+        if paths.is_empty() {
+            paths.push(".".into());
         }
-    }
 
-    for times in times_by_author.values_mut() {
-        times.sort();
+        let mut repos: Vec<gix::Repository> = Vec::with_capacity(paths.len());
+        for path in paths {
+            let repo = gix::open(&path)
+                .with_context(|| format!("failed to open repository {}", path.display()))?;
+            if !repos.iter().any(|other| other.git_dir() == repo.git_dir()) {
+                repos.push(repo);
+            }
+        }
+        Ok(repos)
     }
 
-    Ok(times_by_author)
-}
+    fn config(&self) -> anyhow::Result<EstimatorConfig> {
+        let now = match &self.tz {
+            ReferenceTimeZone::Commit => Zoned::now(),
+            ReferenceTimeZone::Zone(time_zone) => Zoned::now().with_time_zone(time_zone.clone()),
+        };
+        let parse_date = |arg: &str, input: &Option<String>| -> anyhow::Result<Option<Timestamp>> {
+            input
+                .as_deref()
+                .map(|input| git_hours::parse_date(input, &now))
+                .transpose()
+                .with_context(|| format!("invalid value for `--{arg}`"))
+        };
 
-fn estimate_hours(args: &Args, times: &[gix::date::Time]) -> u32 {
-    if times.len() < 2 {
-        return 0;
+        Ok(EstimatorConfig {
+            max_commit_diff: self.max_commit_diff,
+            threshold: self.threshold,
+            first_commit_add: self.first_commit_add,
+            base_hours: self.base_hours,
+            rate: self.rate,
+            author_rates: self
+                .author_rates
+                .iter()
+                .map(|(author, rate)| (author.as_str().into(), *rate))
+                .collect(),
+            merges: self.merges,
+            first_parent: self.first_parent,
+            revisions: self
+                .revisions
+                .iter()
+                .cloned()
+                .chain(
+                    self.branch
+                        .iter()
+                        .map(|branch| format!("refs/heads/{branch}")),
+                )
+                .collect(),
+            all: self.all,
+            remotes: self.remotes,
+            tags: self.tags,
+            since: parse_date("since", &self.since)?,
+            until: parse_date("until", &self.until)?,
+            identity: self.identity,
+            mailmap: !self.no_mailmap,
+            authors: self.authors.clone(),
+            exclude_authors: self.exclude_authors.clone(),
+            co_authors: self.co_authors,
+            timestamp: self.timestamp,
+            threads: self.threads,
+            dedupe_patches: self.dedupe_patches,
+            cache: !self.no_cache,
+            rewrite_threshold: self.rewrite_threshold,
+            exclude_bots: self.exclude_bots,
+            grep: self.grep.clone(),
+            invert_grep: self.invert_grep,
+            issue_pattern: self.issue_pattern.clone(),
+            group_by: self.group_by,
+            pathspecs: self.pathspecs.clone(),
+            exclude_paths: self.exclude_paths.clone(),
+            components: self.components.clone(),
+            path_depth: self.path_depth,
+            component_split: self.component_split,
+            time_zone: self.tz.clone(),
+            allow_shallow: self.allow_shallow,
+            recurse_submodules: self.recurse_submodules,
+            email_aliases: self
+                .email_aliases
+                .iter()
+                .map(|(old, new)| (old.as_str().into(), new.as_str().into()))
+                .collect(),
+        })
     }
+}
 
-    let mut hours = 10f64;
+/// A value parser for the enums of `git_hours`, listing all possible values in the help.
+fn named<T>(names: &'static [&'static str]) -> impl TypedValueParser<Value = T>
+where
+    T: std::str::FromStr<Err = anyhow::Error> + Clone + Send + Sync + 'static,
+{
+    PossibleValuesParser::new(names).try_map(|name| name.parse::<T>())
+}
 
-    for window in times.windows(2) {
-        let (time, next_time) = (window[0], window[1]);
-        let diff_in_minutes = (next_time.seconds - time.seconds) as f64 / 60.0;
+fn parse_alias(input: &str) -> anyhow::Result<(String, String)> {
+    let Some((old, new)) = input.split_once('=') else {
+        bail!("expected an alias in the form `old=new`");
+    };
+    Ok((old.trim().to_string(), new.trim().to_string()))
+}
 
-        if diff_in_minutes < args.max_commit_diff as f64 {
-            hours += diff_in_minutes / 60.0;
-        } else {
-            hours += args.first_commit_add as f64 / 60.0;
-        }
-    }
+fn parse_author_rate(input: &str) -> anyhow::Result<(String, f64)> {
+    let Some((author, rate)) = input.rsplit_once('=') else {
+        bail!("expected an author rate in the form `author=amount`");
+    };
+    let rate = rate.trim().parse().context("invalid amount")?;
+    Ok((author.trim().to_string(), rate))
+}
 
-    hours.round() as u32
+/// Parse the command line arguments `args`, with flags taking optional values, see
+/// [`config::with_flag_values`].
+fn parse_args<'a>(args: impl IntoIterator<Item = &'a OsString>) -> Args {
+    let matches = config::with_flag_values(Args::command()).get_matches_from(args);
+    Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
 }
 
 fn main() -> anyhow::Result<()> {
-    if std::fs::exists(".git/shallow")? {
-        bail!(
-            "Cannot analyze shallow copies. Please run `git fetch --unshallow` before continuing."
-        );
-    }
-
-    let args = Args::parse();
-    let repo = gix::open(&args.path)?;
+    let cli: Vec<_> = std::env::args_os().collect();
+    let args = parse_args(&cli);
+    let mut warnings = Vec::new();
+    let repos = args.repositories(&mut warnings)?;
 
-    let mut authors = Vec::new();
+    let defaults = config::default_args(&Args::command(), repos.first())?;
+    let args = if defaults.is_empty() {
+        args
+    } else {
+        // the actual arguments come last, so they override the defaults
+        parse_args(cli[..1].iter().chain(&defaults).chain(&cli[1..]))
+    };
 
-    for (author, times) in get_commit_times_by_author(&args, &repo)? {
-        authors.push((author, times.len(), estimate_hours(&args, &times)));
+    if let Some(Command::Cache(CacheCommand::Clear)) = &args.command {
+        for repo in &repos {
+            git_hours::clear_cache(repo)?;
+        }
+        return Ok(());
     }
 
+    let config = args.config()?;
     // TODO: make sort configurable (by commits or time)
-    authors.sort_by_key(|(_, _, time)| *time);
-
-    for (author, commits, time) in authors {
-        println!("{author}: {} commits, {} hours", commits, time);
-    }
+    let mut report = git_hours::estimate_repositories(&config, &repos)?;
+    report.warnings.splice(0..0, warnings);
+    let options = Options {
+        format: args.format,
+        sessions: args.sessions,
+    };
+    output::write(&mut std::io::stdout().lock(), &options, &config, &report)?;
 
     Ok(())
 }
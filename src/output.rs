@@ -0,0 +1,571 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use clap::ValueEnum;
+use git_hours::{
+    AuthorEstimate, EstimatorConfig, Identity, ReferenceTimeZone, Report, Session, SessionThreshold,
+};
+use regex::Regex;
+use serde_json::{Value, json};
+
+/// Output format of the report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// A single JSON document with the authors, the total and the effective configuration
+    Json,
+    /// Comma separated values with a header line. Component and issue breakdowns follow the
+    /// total of every author in rows of their own
+    Csv,
+    /// One JSON object per author and line
+    Ndjson,
+}
+
+/// What to write and how.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Output format
+    pub format: Format,
+    /// List the sessions of every author. In CSV and NDJSON, the sessions replace the per-author
+    /// records.
+    pub sessions: bool,
+}
+
+impl Options {
+    /// Whether the authors are written as a matrix of authors and periods.
+    fn matrix(&self, config: &EstimatorConfig) -> bool {
+        config.group_by.is_some()
+            && !self.sessions
+            && matches!(self.format, Format::Text | Format::Csv)
+    }
+}
+
+/// Write the `report` to `out` as configured by `options`.
+///
+/// Formats that have no place for the shallow boundary commits and rewritten commits report them
+/// on stderr instead. Other warnings of the report always go to stderr.
+pub fn write(
+    out: &mut impl Write,
+    options: &Options,
+    config: &EstimatorConfig,
+    report: &Report,
+) -> io::Result<()> {
+    let format = options.format;
+    let estimates = &report.authors;
+    let shallow_commits: Vec<_> = report
+        .shallow_commits
+        .iter()
+        .map(|id| id.to_string())
+        .collect();
+
+    let periods = period_labels(estimates);
+    let components = hour_totals(estimates.iter().flat_map(|estimate| {
+        let components = estimate.components.iter();
+        components.map(|component| (&component.name, component.hours))
+    }));
+    let issues = hour_totals(estimates.iter().flat_map(|estimate| {
+        let issues = estimate.issues.iter();
+        issues.map(|issue| (&issue.key, issue.hours))
+    }));
+
+    match format {
+        Format::Text if options.matrix(config) => {
+            let names: Vec<_> = estimates
+                .iter()
+                .map(|estimate| display_name(config, estimate))
+                .collect();
+            let name_width = names.iter().map(String::len).max().unwrap_or(0).max(6);
+            write!(out, "{:name_width$}", "author")?;
+            for label in &periods {
+                write!(out, "  {label:>8}")?;
+            }
+            writeln!(out, "  {:>8}", "total")?;
+
+            for (estimate, name) in estimates.iter().zip(&names) {
+                write!(out, "{name:name_width$}")?;
+                for label in &periods {
+                    let width = label.len().max(8);
+                    write!(out, "  {:>width$.1}", period_hours(estimate, label))?;
+                }
+                writeln!(out, "  {:>8.1}", estimate.hours)?;
+            }
+            for (estimate, name) in estimates.iter().zip(&names) {
+                if !estimate.components.is_empty() || !estimate.issues.is_empty() {
+                    writeln!(out, "{name}:")?;
+                    write_breakdown(out, estimate)?;
+                }
+            }
+            write_breakdown_totals(out, &components, &issues)?;
+        }
+        Format::Text => {
+            for estimate in estimates {
+                write!(
+                    out,
+                    "{}: {} commits, {} hours",
+                    display_name(config, estimate),
+                    estimate.commits,
+                    estimate.hours.round()
+                )?;
+                if config.threshold == SessionThreshold::Auto {
+                    write!(out, ", threshold {} minutes", estimate.threshold)?;
+                }
+                match estimate.cost {
+                    Some(cost) => writeln!(out, ", cost {cost:.2}")?,
+                    None => writeln!(out)?,
+                }
+                write_breakdown(out, estimate)?;
+                if options.sessions {
+                    for session in &estimate.sessions {
+                        writeln!(
+                            out,
+                            "  {} - {}: {} commits, {} minutes",
+                            format_time(&config.time_zone, session.start),
+                            format_time(&config.time_zone, session.end),
+                            session.commits,
+                            session.minutes.round()
+                        )?;
+                    }
+                }
+            }
+            write_breakdown_totals(out, &components, &issues)?;
+        }
+        Format::Json => {
+            let document = json!({
+                "config": config_json(config),
+                "total": {
+                    "commits": estimates.iter().map(|e| e.commits).sum::<usize>(),
+                    "hours": estimates.iter().map(|e| e.hours).sum::<f64>(),
+                    "sessions": estimates.iter().map(|e| e.sessions.len()).sum::<usize>(),
+                    "cost": total_cost(estimates),
+                },
+                "authors": estimates
+                    .iter()
+                    .map(|estimate| estimate_json(config, estimate, options.sessions))
+                    .collect::<Vec<_>>(),
+                "periods": periods,
+                "components": hours_json(components.iter().map(|(name, hours)| (name, *hours))),
+                "issues": hours_json(issues.iter().map(|(key, hours)| (key, *hours))),
+                "shallow_commits": shallow_commits,
+                "rewritten_commits": report
+                    .rewritten_commits
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>(),
+            });
+            serde_json::to_writer_pretty(&mut *out, &document)?;
+            writeln!(out)?;
+        }
+        Format::Csv if options.sessions => {
+            writeln!(out, "author,name,start,end,commits,minutes")?;
+            for estimate in estimates {
+                for session in &estimate.sessions {
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{}",
+                        csv_field(&estimate.author.to_string()),
+                        csv_field(&estimate.name.to_string()),
+                        format_time(&config.time_zone, session.start),
+                        format_time(&config.time_zone, session.end),
+                        session.commits,
+                        session.minutes,
+                    )?;
+                }
+            }
+        }
+        Format::Csv if options.matrix(config) => {
+            // like in the per-author records, breakdowns are listed in rows of their own, which
+            // only fill the total as they aren't split by period
+            let breakdown = config.has_components() || config.issue_pattern.is_some();
+            write!(out, "author,name")?;
+            if breakdown {
+                write!(out, ",kind,key")?;
+            }
+            for label in &periods {
+                write!(out, ",{label}")?;
+            }
+            writeln!(out, ",total")?;
+            for estimate in estimates {
+                let author = csv_field(&estimate.author.to_string());
+                let name = csv_field(&estimate.name.to_string());
+                write!(out, "{author},{name}")?;
+                if breakdown {
+                    write!(out, ",total,")?;
+                }
+                for label in &periods {
+                    write!(out, ",{}", period_hours(estimate, label))?;
+                }
+                writeln!(out, ",{}", estimate.hours)?;
+
+                let components = (estimate.components.iter())
+                    .map(|component| ("component", &component.name, component.hours));
+                let issues =
+                    (estimate.issues.iter()).map(|issue| ("issue", &issue.key, issue.hours));
+                for (kind, key, hours) in components.chain(issues) {
+                    let empty = ",".repeat(periods.len());
+                    writeln!(
+                        out,
+                        "{author},{name},{kind},{}{empty},{hours}",
+                        csv_field(key)
+                    )?;
+                }
+            }
+        }
+        Format::Csv => {
+            // breakdowns are listed in rows of their own after the total of every author
+            let breakdown = config.has_components() || config.issue_pattern.is_some();
+            let adaptive = config.threshold == SessionThreshold::Auto;
+            let billed = config.rate.is_some() || !config.author_rates.is_empty();
+            write!(out, "author,name")?;
+            if breakdown {
+                write!(out, ",kind,key")?;
+            }
+            write!(out, ",commits,hours,sessions,first_commit,last_commit")?;
+            if adaptive {
+                write!(out, ",threshold")?;
+            }
+            writeln!(out, "{}", if billed { ",cost" } else { "" })?;
+            for estimate in estimates {
+                let author = csv_field(&estimate.author.to_string());
+                let name = csv_field(&estimate.name.to_string());
+                write!(out, "{author},{name}")?;
+                if breakdown {
+                    write!(out, ",total,")?;
+                }
+                let time = |time| format_time(&config.time_zone, time);
+                let (first_commit, last_commit) = (
+                    estimate.first_commit.map(time),
+                    estimate.last_commit.map(time),
+                );
+                write!(
+                    out,
+                    ",{},{},{},{},{}",
+                    estimate.commits,
+                    estimate.hours,
+                    estimate.sessions.len(),
+                    first_commit.unwrap_or_default(),
+                    last_commit.unwrap_or_default(),
+                )?;
+                if adaptive {
+                    write!(out, ",{}", estimate.threshold)?;
+                }
+                match estimate.cost {
+                    Some(cost) if billed => writeln!(out, ",{cost}")?,
+                    _ if billed => writeln!(out, ",")?,
+                    _ => writeln!(out)?,
+                }
+
+                let components = (estimate.components.iter())
+                    .map(|component| ("component", &component.name, component.hours));
+                let issues =
+                    (estimate.issues.iter()).map(|issue| ("issue", &issue.key, issue.hours));
+                for (kind, key, hours) in components.chain(issues) {
+                    write!(out, "{author},{name},{kind},{},,{hours},,,", csv_field(key))?;
+                    if adaptive {
+                        write!(out, ",")?;
+                    }
+                    writeln!(out, "{}", if billed { "," } else { "" })?;
+                }
+            }
+        }
+        Format::Ndjson if options.sessions => {
+            for estimate in estimates {
+                for session in &estimate.sessions {
+                    let mut record = json!({
+                        "author": estimate.author.to_string(),
+                        "name": estimate.name.to_string(),
+                    });
+                    record.as_object_mut().expect("record is an object").extend(
+                        session_json(config, session)
+                            .as_object()
+                            .cloned()
+                            .unwrap_or_default(),
+                    );
+                    serde_json::to_writer(&mut *out, &record)?;
+                    writeln!(out)?;
+                }
+            }
+        }
+        Format::Ndjson => {
+            for estimate in estimates {
+                serde_json::to_writer(&mut *out, &estimate_json(config, estimate, false))?;
+                writeln!(out)?;
+            }
+        }
+    }
+
+    if !shallow_commits.is_empty() {
+        let shallow_commits = shallow_commits.join(", ");
+        match format {
+            Format::Text => writeln!(
+                out,
+                "History is truncated at shallow commits: {shallow_commits}"
+            )?,
+            Format::Json => {}
+            Format::Csv | Format::Ndjson => {
+                eprintln!("warning: history is truncated at shallow commits: {shallow_commits}")
+            }
+        }
+    }
+
+    if !report.rewritten_commits.is_empty() {
+        let count = report.rewritten_commits.len();
+        let threshold = config.rewrite_threshold;
+        let mut examples: Vec<_> = report
+            .rewritten_commits
+            .iter()
+            .take(5)
+            .map(|id| id.to_hex_with_len(12).to_string())
+            .collect();
+        if count > examples.len() {
+            examples.push("...".to_string());
+        }
+        let examples = examples.join(", ");
+        let message = format!(
+            "{count} commits have author and committer times more than {threshold} minutes apart, \
+             their history was likely rewritten: {examples}"
+        );
+        match format {
+            Format::Text => writeln!(out, "{message}")?,
+            Format::Json => {}
+            Format::Csv | Format::Ndjson => eprintln!("warning: {message}"),
+        }
+    }
+
+    for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    Ok(())
+}
+
+fn estimate_json(config: &EstimatorConfig, estimate: &AuthorEstimate, sessions: bool) -> Value {
+    let mut record = json!({
+        "author": estimate.author.to_string(),
+        "name": estimate.name.to_string(),
+        "commits": estimate.commits,
+        "hours": estimate.hours,
+        "sessions": estimate.sessions.len(),
+        "first_commit": estimate.first_commit.map(|time| format_time(&config.time_zone, time)),
+        "last_commit": estimate.last_commit.map(|time| format_time(&config.time_zone, time)),
+    });
+    if config.threshold == SessionThreshold::Auto {
+        record["threshold"] = estimate.threshold.into();
+    }
+    if let Some(cost) = estimate.cost {
+        record["cost"] = cost.into();
+    }
+    if sessions {
+        record["session_list"] = estimate
+            .sessions
+            .iter()
+            .map(|session| session_json(config, session))
+            .collect();
+    }
+    if !estimate.periods.is_empty() {
+        record["periods"] = estimate
+            .periods
+            .iter()
+            .map(|period| (period.label.clone(), Value::from(period.hours)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+    if !estimate.components.is_empty() {
+        record["components"] = hours_json(
+            estimate
+                .components
+                .iter()
+                .map(|component| (&component.name, component.hours)),
+        );
+    }
+    if !estimate.issues.is_empty() {
+        record["issues"] = hours_json(
+            estimate
+                .issues
+                .iter()
+                .map(|issue| (&issue.key, issue.hours)),
+        );
+    }
+    record
+}
+
+/// The total cost of all authors with a rate, or `None` if no author has one.
+fn total_cost(estimates: &[AuthorEstimate]) -> Option<f64> {
+    let mut costs = estimates
+        .iter()
+        .filter_map(|estimate| estimate.cost)
+        .peekable();
+    costs.peek()?;
+    Some(costs.sum())
+}
+
+fn hours_json<'a>(hours: impl Iterator<Item = (&'a String, f64)>) -> Value {
+    hours
+        .map(|(name, hours)| (name.clone(), Value::from(hours)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn session_json(config: &EstimatorConfig, session: &Session) -> Value {
+    json!({
+        "start": format_time(&config.time_zone, session.start),
+        "end": format_time(&config.time_zone, session.end),
+        "commits": session.commits,
+        "minutes": session.minutes,
+    })
+}
+
+fn config_json(config: &EstimatorConfig) -> Value {
+    json!({
+        "max_commit_diff": config.max_commit_diff,
+        "threshold": config.threshold.to_string(),
+        "first_commit_add": config.first_commit_add,
+        "base_hours": config.base_hours,
+        "rate": config.rate,
+        "author_rates": config
+            .author_rates
+            .iter()
+            .map(|(author, rate)| (author.to_string(), Value::from(*rate)))
+            .collect::<BTreeMap<_, _>>(),
+        "merges": config.merges.to_string(),
+        "first_parent": config.first_parent,
+        "revisions": config.revisions,
+        "all": config.all,
+        "remotes": config.remotes,
+        "tags": config.tags,
+        "since": config.since.map(|since| since.to_string()),
+        "until": config.until.map(|until| until.to_string()),
+        "identity": config.identity.to_string(),
+        "group_by": config.group_by.map(|period| period.to_string()),
+        "pathspecs": config.pathspecs,
+        "exclude_paths": config.exclude_paths,
+        "components": config
+            .components
+            .iter()
+            .map(|component| (component.name.clone(), Value::from(component.glob.clone())))
+            .collect::<serde_json::Map<_, _>>(),
+        "path_depth": config.path_depth,
+        "component_split": config.component_split.to_string(),
+        "time_zone": config.time_zone.to_string(),
+        "mailmap": config.mailmap,
+        "authors": config.authors.iter().map(Regex::as_str).collect::<Vec<_>>(),
+        "exclude_authors": config.exclude_authors.iter().map(Regex::as_str).collect::<Vec<_>>(),
+        "co_authors": config.co_authors.to_string(),
+        "timestamp": config.timestamp.to_string(),
+        "threads": config.threads(),
+        "dedupe_patches": config.dedupe_patches,
+        "cache": config.cache,
+        "rewrite_threshold": config.rewrite_threshold,
+        "exclude_bots": config.exclude_bots,
+        "grep": config.grep.iter().map(Regex::as_str).collect::<Vec<_>>(),
+        "invert_grep": config.invert_grep,
+        "issue_pattern": config.issue_pattern.as_ref().map(Regex::as_str),
+        "allow_shallow": config.allow_shallow,
+        "recurse_submodules": config.recurse_submodules,
+        "email_aliases": config
+            .email_aliases
+            .iter()
+            .map(|(old, new)| (old.to_string(), Value::from(new.to_string())))
+            .collect::<serde_json::Map<_, _>>(),
+    })
+}
+
+/// How an author is shown in human readable output.
+fn display_name(config: &EstimatorConfig, estimate: &AuthorEstimate) -> String {
+    // the other identities already contain the name
+    if config.identity == Identity::Email {
+        format!("{} ({})", estimate.author, estimate.name)
+    } else {
+        estimate.author.to_string()
+    }
+}
+
+/// The labels of all periods any author worked in, sorted by period.
+fn period_labels(estimates: &[AuthorEstimate]) -> Vec<String> {
+    let periods: BTreeMap<_, _> = estimates
+        .iter()
+        .flat_map(|estimate| &estimate.periods)
+        .map(|period| (period.first_day, &period.label))
+        .collect();
+    periods.into_values().cloned().collect()
+}
+
+/// Sum the `hours` by name, sorted by hours in descending order.
+fn hour_totals<'a>(hours: impl Iterator<Item = (&'a String, f64)>) -> Vec<(String, f64)> {
+    let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for (name, hours) in hours {
+        *totals.entry(name).or_default() += hours;
+    }
+    let mut totals: Vec<_> = totals
+        .into_iter()
+        .map(|(name, hours)| (name.to_string(), hours))
+        .collect();
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+    totals
+}
+
+/// The hours of an author in the period with the given `label`.
+fn period_hours(estimate: &AuthorEstimate, label: &str) -> f64 {
+    estimate
+        .periods
+        .iter()
+        .find(|period| period.label == label)
+        .map_or(0.0, |period| period.hours)
+}
+
+/// Write the hours an author spent on each component and issue as indented lines.
+fn write_breakdown(out: &mut impl Write, estimate: &AuthorEstimate) -> io::Result<()> {
+    for component in &estimate.components {
+        writeln!(out, "  {}: {:.1} hours", component.name, component.hours)?;
+    }
+    for issue in &estimate.issues {
+        writeln!(out, "  {}: {:.1} hours", issue.key, issue.hours)?;
+    }
+    Ok(())
+}
+
+/// Write the hours of all authors on each component and issue, if any.
+fn write_breakdown_totals(
+    out: &mut impl Write,
+    components: &[(String, f64)],
+    issues: &[(String, f64)],
+) -> io::Result<()> {
+    if !components.is_empty() {
+        writeln!(out, "components:")?;
+        for (name, hours) in components {
+            writeln!(out, "  {name}: {hours:.1} hours")?;
+        }
+    }
+    if !issues.is_empty() {
+        writeln!(out, "issues:")?;
+        for (key, hours) in issues {
+            writeln!(out, "  {key}: {hours:.1} hours")?;
+        }
+    }
+    Ok(())
+}
+
+/// Format `time` as RFC 3339 timestamp in the given time zone.
+fn format_time(time_zone: &ReferenceTimeZone, time: gix::date::Time) -> String {
+    time_zone
+        .to_zoned(time)
+        .map(|zoned| {
+            zoned
+                .timestamp()
+                .display_with_offset(zoned.offset())
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Quote `field` if it contains characters that are special in CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
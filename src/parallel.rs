@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many items a thread takes at once.
+const CHUNK_SIZE: usize = 16;
+
+/// Apply `f` to all `items` on up to `threads` threads and return the results in the order of
+/// the items.
+///
+/// Every thread creates its own state with `init` and passes it to `f`, e.g. a thread-local
+/// repository and the buffers to diff with. Work is handed out in small chunks, so threads that
+/// get cheap items take on more of them.
+pub(crate) fn map<T, S, R>(
+    threads: usize,
+    items: &[T],
+    init: impl Fn() -> S + Sync,
+    f: impl Fn(&mut S, &T) -> R + Sync,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let threads = threads.min(items.len().div_ceil(CHUNK_SIZE)).max(1);
+    if threads == 1 {
+        let mut state = init();
+        return items.iter().map(|item| f(&mut state, item)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut state = init();
+                    let mut results = Vec::new();
+                    loop {
+                        let start = next.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+                        if start >= items.len() {
+                            break results;
+                        }
+                        let end = (start + CHUNK_SIZE).min(items.len());
+                        for (i, item) in items[start..end].iter().enumerate() {
+                            results.push((start + i, f(&mut state, item)));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let worker_results = match worker.join() {
+                Ok(worker_results) => worker_results,
+                Err(panic) => std::panic::resume_unwind(panic),
+            };
+            for (i, result) in worker_results {
+                results[i] = Some(result);
+            }
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.expect("every item was processed"))
+        .collect()
+}
+
+/// The number of threads to use if none is configured.
+pub(crate) fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+
+use jiff::{Span, Timestamp, civil::Date};
+
+use crate::{CommitInfo, EstimatorConfig, ReferenceTimeZone, Session};
+
+/// A calendar period to group hours by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// A calendar day.
+    Day,
+    /// An ISO 8601 week, starting on Monday.
+    Week,
+    /// A calendar month.
+    Month,
+    /// A quarter of a year, starting in January, April, July or October.
+    Quarter,
+    /// A calendar year.
+    Year,
+}
+
+named_enum!(Period {
+    Day => "day",
+    Week => "week",
+    Month => "month",
+    Quarter => "quarter",
+    Year => "year",
+});
+
+impl Period {
+    /// The first day of the period containing `date`.
+    fn first_day(self, date: Date) -> anyhow::Result<Date> {
+        Ok(match self {
+            Period::Day => date,
+            Period::Week => date.iso_week_date().first_of_week()?.date(),
+            Period::Month => date.first_of_month(),
+            Period::Quarter => Date::new(date.year(), (date.month() - 1) / 3 * 3 + 1, 1)?,
+            Period::Year => date.first_of_year(),
+        })
+    }
+
+    fn span(self) -> Span {
+        match self {
+            Period::Day => Span::new().days(1),
+            Period::Week => Span::new().weeks(1),
+            Period::Month => Span::new().months(1),
+            Period::Quarter => Span::new().months(3),
+            Period::Year => Span::new().years(1),
+        }
+    }
+
+    /// A label for the period starting at `first_day`, e.g. `2024-W05` for a week.
+    fn label(self, first_day: Date) -> String {
+        match self {
+            Period::Day => first_day.to_string(),
+            Period::Week => {
+                let week = first_day.iso_week_date();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => format!("{}-{:02}", first_day.year(), first_day.month()),
+            Period::Quarter => format!("{}-Q{}", first_day.year(), (first_day.month() - 1) / 3 + 1),
+            Period::Year => first_day.year().to_string(),
+        }
+    }
+}
+
+/// The hours of an author in a single period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodHours {
+    /// Label of the period, e.g. `2024-03` for a month or `2024-W05` for a week.
+    pub label: String,
+    /// First day of the period.
+    pub first_day: Date,
+    /// Hours worked in the period.
+    pub hours: f64,
+}
+
+/// Split the hours of `sessions` into the calendar periods they fall into, as seen in
+/// `time_zone`. With [`ReferenceTimeZone::Commit`], the offset of the first commit of a session
+/// applies to the whole session.
+///
+/// The time between subsequent commits of a session is split at period boundaries, and the first
+/// commit bonus belongs to the period of the session's first commit. `commits` are the commits
+/// the sessions were split from, and the time credited for each is scaled by its
+/// [`share`](CommitInfo::share). The
+/// [`base_hours`](EstimatorConfig::base_hours) are not attributed to any period. The result is
+/// sorted by period.
+pub fn split_periods(
+    config: &EstimatorConfig,
+    period: Period,
+    time_zone: &ReferenceTimeZone,
+    commits: &[CommitInfo],
+    sessions: &[Session],
+) -> anyhow::Result<Vec<PeriodHours>> {
+    let mut periods = BTreeMap::new();
+    let mut credit =
+        |session: &Session, share: f64, at: i64, until: Option<i64>| -> anyhow::Result<i64> {
+            let time_zone = time_zone.for_time(session.start);
+            let zoned = Timestamp::from_second(at)?.to_zoned(time_zone.clone());
+            let first_day = period.first_day(zoned.date())?;
+            let start = first_day.to_zoned(time_zone)?;
+            let end = start.checked_add(period.span())?.timestamp().as_second();
+            let hours = share
+                * match until {
+                    Some(until) => (until.min(end) - at) as f64 / 60.0 / 60.0,
+                    None => config.first_commit_add as f64 / 60.0,
+                };
+
+            periods
+                .entry(first_day)
+                .or_insert_with(|| PeriodHours {
+                    label: period.label(first_day),
+                    first_day,
+                    hours: 0.0,
+                })
+                .hours += hours;
+            Ok(end)
+        };
+
+    for session in sessions {
+        let commits = &commits[session.range.clone()];
+        if config.contains(session.start) {
+            credit(session, commits[0].share, session.start.seconds, None)?;
+        }
+
+        for pair in commits.windows(2) {
+            let (start, end) = config.clip(pair[0].time.seconds, pair[1].time.seconds);
+            let mut at = start;
+            while at < end {
+                at = credit(session, pair[1].share, at, Some(end))?.min(end);
+            }
+        }
+    }
+
+    Ok(periods.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use gix::{ObjectId, hash::Kind};
+    use jiff::civil::date;
+
+    use super::*;
+    use crate::split_sessions;
+
+    fn commit(time: &str) -> CommitInfo {
+        let time: Timestamp = time.parse().unwrap();
+        CommitInfo {
+            id: ObjectId::null(Kind::Sha1),
+            time: gix::date::Time::new(time.as_second(), 0),
+            rewritten: false,
+            share: 1.0,
+            components: vec![],
+            patch_id: None,
+            issues: vec![],
+        }
+    }
+
+    /// The hours of `commits` by period label, with a first commit bonus of 30 minutes.
+    fn hours(period: Period, time_zone: &str, commits: &[CommitInfo]) -> Vec<(String, f64)> {
+        let config = EstimatorConfig {
+            first_commit_add: 30,
+            ..EstimatorConfig::default()
+        };
+        let time_zone: ReferenceTimeZone = time_zone.parse().unwrap();
+        let sessions = split_sessions(&config, 120, commits);
+        split_periods(&config, period, &time_zone, commits, &sessions)
+            .unwrap()
+            .into_iter()
+            .map(|period| (period.label, period.hours))
+            .collect()
+    }
+
+    fn periods(labels: &[(&str, f64)]) -> Vec<(String, f64)> {
+        (labels.iter())
+            .map(|(label, hours)| (label.to_string(), *hours))
+            .collect()
+    }
+
+    #[test]
+    fn iso_weeks() {
+        let week = |date| {
+            let first_day = Period::Week.first_day(date).unwrap();
+            (first_day, Period::Week.label(first_day))
+        };
+        assert_eq!(
+            week(date(2024, 3, 6)),
+            (date(2024, 3, 4), "2024-W10".into())
+        );
+        // the week of the year's first thursday is the first week
+        assert_eq!(
+            week(date(2024, 12, 30)),
+            (date(2024, 12, 30), "2025-W01".into())
+        );
+        assert_eq!(
+            week(date(2025, 1, 5)),
+            (date(2024, 12, 30), "2025-W01".into())
+        );
+        assert_eq!(
+            week(date(2021, 1, 3)),
+            (date(2020, 12, 28), "2020-W53".into())
+        );
+    }
+
+    #[test]
+    fn quarters() {
+        let quarter = |date| {
+            let first_day = Period::Quarter.first_day(date).unwrap();
+            (first_day, Period::Quarter.label(first_day))
+        };
+        assert_eq!(
+            quarter(date(2024, 1, 1)),
+            (date(2024, 1, 1), "2024-Q1".into())
+        );
+        assert_eq!(
+            quarter(date(2024, 5, 15)),
+            (date(2024, 4, 1), "2024-Q2".into())
+        );
+        assert_eq!(
+            quarter(date(2024, 9, 30)),
+            (date(2024, 7, 1), "2024-Q3".into())
+        );
+        assert_eq!(
+            quarter(date(2024, 12, 31)),
+            (date(2024, 10, 1), "2024-Q4".into())
+        );
+    }
+
+    #[test]
+    fn session_split_at_midnight() {
+        let commits = [
+            commit("2024-03-05T23:00:00Z"),
+            commit("2024-03-06T00:30:00Z"),
+            commit("2024-03-06T01:00:00Z"),
+        ];
+        assert_eq!(
+            hours(Period::Day, "utc", &commits),
+            periods(&[("2024-03-05", 1.5), ("2024-03-06", 1.0)])
+        );
+        // in Berlin, all of it happens on the 6th
+        assert_eq!(
+            hours(Period::Day, "Europe/Berlin", &commits),
+            periods(&[("2024-03-06", 2.5)])
+        );
+    }
+
+    #[test]
+    fn session_split_at_month_boundary() {
+        let commits = [
+            commit("2024-01-31T23:30:00Z"),
+            commit("2024-02-01T00:30:00Z"),
+        ];
+        assert_eq!(
+            hours(Period::Month, "utc", &commits),
+            periods(&[("2024-01", 1.0), ("2024-02", 0.5)])
+        );
+    }
+
+    #[test]
+    fn day_across_daylight_saving_time() {
+        // Europe/Berlin skips from 02:00 to 03:00 on 2024-03-31, so the day has 23 hours. A
+        // commit every hour from 23:30 on the 30th to 00:30 on April 1st, local time.
+        let start: Timestamp = "2024-03-30T22:30:00Z".parse().unwrap();
+        let commits: Vec<_> = (0..=24)
+            .map(|hour| {
+                commit(
+                    &start
+                        .checked_add(Span::new().hours(hour))
+                        .unwrap()
+                        .to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            hours(Period::Day, "Europe/Berlin", &commits),
+            periods(&[
+                ("2024-03-30", 1.0),
+                ("2024-03-31", 23.0),
+                ("2024-04-01", 0.5)
+            ])
+        );
+        // in UTC, every day has 24 hours
+        assert_eq!(
+            hours(Period::Day, "utc", &commits),
+            periods(&[("2024-03-30", 2.0), ("2024-03-31", 22.5)])
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+/// Find all git repositories in `dir` and its subdirectories, sorted by path.
+///
+/// Both repositories with a worktree and bare repositories are found. Directories inside a
+/// repository aren't searched, so nested repositories like submodules are not part of the result,
+/// and symbolic links aren't followed.
+///
+/// Fails if `dir` can't be read. Subdirectories that can't be read, e.g. for lack of permission
+/// or because they were deleted meanwhile, are skipped and reported in `warnings`.
+pub fn find_repositories(dir: &Path, warnings: &mut Vec<String>) -> anyhow::Result<Vec<PathBuf>> {
+    let mut repositories = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        if current.join(".git").exists() || gix::discover::is_git(&current).is_ok() {
+            repositories.push(current);
+            continue;
+        }
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(err) if current != dir => {
+                warnings.push(format!("skipped directory {}: {err}", current.display()));
+                continue;
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read directory {}", current.display()));
+            }
+        };
+        for entry in entries {
+            match entry.and_then(|entry| Ok((entry.file_type()?, entry.path()))) {
+                Ok((file_type, path)) if file_type.is_dir() => pending.push(path),
+                Ok(_) => {}
+                Err(err) => {
+                    warnings.push(format!("skipped an entry of {}: {err}", current.display()));
+                }
+            }
+        }
+    }
+    repositories.sort();
+    Ok(repositories)
+}
@@ -0,0 +1,120 @@
+use std::ops::RangeInclusive;
+
+use crate::CommitInfo;
+
+/// The least number of gaps between the commits of an author to derive a threshold from.
+pub const MIN_GAPS: usize = 10;
+
+/// The range derived thresholds are clamped to, in minutes.
+pub const THRESHOLD_RANGE: RangeInclusive<u32> = 15..=12 * 60;
+
+/// Derive the maximum gap in minutes between two commits of a session from the gaps between the
+/// sorted `commits` of a single author.
+///
+/// On a logarithmic scale, the gaps between commits form two clusters: commits within a session
+/// minutes apart, and breaks between sessions hours to days apart. The threshold lies in the
+/// valley between them, which is found with Otsu's method: the gaps are split where the variance
+/// of the log-gaps within both sides is smallest. The threshold is the geometric mean of the gaps
+/// next to the split, clamped to [`THRESHOLD_RANGE`].
+///
+/// Commits made at the same time are ignored. Returns `None` if there are fewer than
+/// [`MIN_GAPS`] gaps.
+pub fn adaptive_threshold(commits: &[CommitInfo]) -> Option<u32> {
+    let mut gaps: Vec<f64> = commits
+        .windows(2)
+        .map(|pair| pair[1].time.seconds - pair[0].time.seconds)
+        .filter(|gap| *gap > 0)
+        .map(|gap| (gap as f64).ln())
+        .collect();
+    if gaps.len() < MIN_GAPS {
+        return None;
+    }
+    gaps.sort_by(f64::total_cmp);
+
+    let total: f64 = gaps.iter().sum();
+    let mut below = 0.0;
+    let mut best = None;
+    let mut best_variance = f64::NEG_INFINITY;
+    for split in 1..gaps.len() {
+        below += gaps[split - 1];
+        if gaps[split - 1] == gaps[split] {
+            continue;
+        }
+        // maximizing the variance between both sides minimizes the variance within them
+        let (count_below, count_above) = (split as f64, (gaps.len() - split) as f64);
+        let mean_below = below / count_below;
+        let mean_above = (total - below) / count_above;
+        let variance = count_below * count_above * (mean_above - mean_below).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            best = Some(split);
+        }
+    }
+
+    let split = best?;
+    let seconds = ((gaps[split - 1] + gaps[split]) / 2.0).exp();
+    let minutes = (seconds / 60.0).round() as u32;
+    Some(minutes.clamp(*THRESHOLD_RANGE.start(), *THRESHOLD_RANGE.end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use gix::{ObjectId, date::Time, hash::Kind};
+
+    use super::*;
+
+    /// Commits following each other by the `gaps` in seconds.
+    fn commits(gaps: &[i64]) -> Vec<CommitInfo> {
+        let mut seconds = 1_700_000_000;
+        let mut commits = vec![seconds];
+        for gap in gaps {
+            seconds += gap;
+            commits.push(seconds);
+        }
+        commits
+            .into_iter()
+            .map(|seconds| CommitInfo {
+                id: ObjectId::null(Kind::Sha1),
+                time: Time::new(seconds, 0),
+                rewritten: false,
+                share: 1.0,
+                components: vec![],
+                patch_id: None,
+                issues: vec![],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_between_clusters() {
+        let (minute, hour) = (60, 60 * 60);
+        let session = [5 * minute, 10 * minute, 20 * minute];
+        let breaks = [12 * hour, 24 * hour, 48 * hour];
+        let gaps = [session, session, breaks, session, breaks].concat();
+        // the geometric mean of 20 minutes and 12 hours
+        assert_eq!(adaptive_threshold(&commits(&gaps)), Some(120));
+    }
+
+    #[test]
+    fn clamped_to_range() {
+        let gaps = [[10, 20].repeat(5), [60, 120].repeat(5)].concat();
+        assert_eq!(
+            adaptive_threshold(&commits(&gaps)),
+            Some(*THRESHOLD_RANGE.start())
+        );
+        let gaps = [[3_600, 7_200].repeat(5), [864_000, 1_728_000].repeat(5)].concat();
+        assert_eq!(
+            adaptive_threshold(&commits(&gaps)),
+            Some(*THRESHOLD_RANGE.end())
+        );
+    }
+
+    #[test]
+    fn too_few_gaps() {
+        let gaps = [300, 600, 86_400].repeat(3);
+        assert_eq!(adaptive_threshold(&commits(&gaps)), None);
+        // commits made at the same time don't count
+        let gaps = [[300, 600, 86_400].repeat(3), vec![0; 5]].concat();
+        assert_eq!(adaptive_threshold(&commits(&gaps)), None);
+    }
+}